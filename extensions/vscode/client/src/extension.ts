@@ -25,7 +25,9 @@ export function activate(context: ExtensionContext) {
 
     const clientOptions: LanguageClientOptions = {
         documentSelector: [{ scheme: 'file', language: 'c' }, { scheme: 'file', language: 'cpp' }],
+        initializationOptions: workspace.getConfiguration('qmkLsp'),
         synchronize: {
+            configurationSection: 'qmkLsp',
             fileEvents: workspace.createFileSystemWatcher('**/.clientrc'),
         },
     };
@@ -0,0 +1,40 @@
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::PathBuf;
+
+/// Client settings, from `initializationOptions` or `workspace/didChangeConfiguration`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    /// A local `qmk_firmware` checkout used to resolve built-in keycodes and APIs.
+    pub firmware_path: Option<PathBuf>,
+    /// What the inlay hint in front of each key shows.
+    pub inlay_hints: InlayHintStyle,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InlayHintStyle {
+    /// Position in the layout macro's argument list: `L3`.
+    #[default]
+    Index,
+    /// Electrical position from info.json: `R0C5`.
+    Matrix,
+    /// The physical key's `label` from info.json.
+    Label,
+}
+
+/// Section the VS Code client nests its settings under.
+pub const SETTINGS_SECTION: &str = "qmkLsp";
+
+impl Settings {
+    /// Reads settings either nested under [`SETTINGS_SECTION`] or at the top level.
+    /// Anything unparseable falls back to the defaults.
+    pub fn from_value(value: Option<&Value>) -> Settings {
+        let Some(value) = value else {
+            return Settings::default();
+        };
+        let value = value.get(SETTINGS_SECTION).unwrap_or(value);
+        serde_json::from_value(value.clone()).unwrap_or_default()
+    }
+}
@@ -1,12 +1,32 @@
 use dashmap::DashMap;
-use tower_lsp::lsp_types::Url;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tower_lsp::lsp_types::{Position, Range, Url};
+use crate::parser::keycodes::KeycodeMap;
+use crate::parser::macros::MacroMap;
 use crate::parser::layers::Layer;
 use crate::parser::custom::CustomKeycode;
+use crate::parser::enums::CEnum;
+use crate::parser::tables::Table;
+use crate::parser::tokens::Token;
 
 pub struct DocumentData {
     pub content: String,
     pub layers: Vec<Layer>,
     pub custom_keycodes: Vec<CustomKeycode>,
+    pub enums: Vec<CEnum>,
+    /// The `tap_dance_actions` table, if the document defines one.
+    pub tap_dances: Option<Table>,
+    /// The `key_combos` table, if the document defines one.
+    pub combos: Option<Table>,
+    pub tokens: Vec<Token>,
+    pub line_index: LineIndex,
+    /// Built-in keycodes merged with any `.qmk-lsp/keycodes.json` overlay.
+    pub keycodes: Arc<KeycodeMap>,
+    /// Built-in macros merged with any `.qmk-lsp/macros.json` overlay.
+    pub macros: Arc<MacroMap>,
+    /// The `.qmk-lsp` directories `keycodes` and `macros` were merged from, if any.
+    pub overlay_dirs: Vec<PathBuf>,
 }
 
 pub struct DocumentStore {
@@ -20,3 +40,57 @@ impl DocumentStore {
         }
     }
 }
+
+impl Default for DocumentStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Converts between byte offsets and LSP (UTF-16) positions.
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(content: &str) -> Self {
+        let mut line_starts = vec![0];
+        for (idx, c) in content.char_indices() {
+            if c == '\n' {
+                line_starts.push(idx + 1);
+            }
+        }
+        LineIndex { line_starts }
+    }
+
+    pub fn position(&self, content: &str, offset: usize) -> Position {
+        let offset = offset.min(content.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let line_start = self.line_starts[line];
+        let character: usize = content[line_start..offset].chars().map(|c| c.len_utf16()).sum();
+        Position::new(line as u32, character as u32)
+    }
+
+    pub fn range(&self, content: &str, span: std::ops::Range<usize>) -> Range {
+        Range::new(self.position(content, span.start), self.position(content, span.end))
+    }
+
+    pub fn offset(&self, content: &str, position: Position) -> usize {
+        let Some(&line_start) = self.line_starts.get(position.line as usize) else {
+            return content.len();
+        };
+        let line_end = self
+            .line_starts
+            .get(position.line as usize + 1)
+            .map_or(content.len(), |&next| next - 1);
+
+        let mut utf16_count = 0;
+        for (idx, c) in content[line_start..line_end].char_indices() {
+            if utf16_count >= position.character as usize {
+                return line_start + idx;
+            }
+            utf16_count += c.len_utf16();
+        }
+        line_end
+    }
+}
@@ -0,0 +1,150 @@
+use crate::document::DocumentData;
+use crate::legend::{key_legend, LegendContext};
+use crate::parser::expr::{parse_key_expr, KeyExpr};
+use crate::parser::info_json::{layout_rows, KeyboardInfo, LayoutKey};
+use crate::parser::layers::Layer;
+use crate::parser::macros::ParamKind;
+use crate::workspace::layout_symbols;
+use tower_lsp::lsp_types::*;
+
+pub const PREVIEW_LAYER_COMMAND: &str = "qmk.previewLayer";
+pub const OPEN_LAYOUT_COMMAND: &str = "qmk.openLayout";
+
+/// The keyboard's info.json: where it lives, its text and its parse.
+pub struct InfoJson<'a> {
+    pub uri: &'a Url,
+    pub content: &'a str,
+    pub info: &'a KeyboardInfo,
+}
+
+/// A summary, a preview command and (when info.json declares the layout) an
+/// open-layout command above every layer.
+pub fn code_lenses(uri: &Url, doc: &DocumentData, info: Option<&InfoJson>) -> Vec<CodeLens> {
+    let layout_ranges = info.map(|i| layout_symbols(i.content, "")).unwrap_or_default();
+    let mut lenses = Vec::new();
+
+    for layer in &doc.layers {
+        let range = doc.line_index.range(&doc.content, layer.name_span.clone());
+        let expected = info
+            .and_then(|i| i.info.layouts.get(&layer.macro_name))
+            .map(|l| l.layout.len());
+
+        lenses.push(lens(range, layer_summary(doc, layer, expected), "", None));
+        lenses.push(lens(
+            range,
+            "Preview".to_string(),
+            PREVIEW_LAYER_COMMAND,
+            Some(vec![serde_json::json!(uri), serde_json::json!(layer.name)]),
+        ));
+        if let Some(info) = info
+            && let Some(symbol) = layout_ranges.iter().find(|s| s.name == layer.macro_name)
+        {
+            lenses.push(lens(
+                range,
+                format!("Open {} in {}", layer.macro_name, info.uri.path().rsplit('/').next().unwrap_or("info.json")),
+                OPEN_LAYOUT_COMMAND,
+                Some(vec![serde_json::json!(info.uri), serde_json::json!(symbol.range)]),
+            ));
+        }
+    }
+    lenses
+}
+
+fn lens(range: Range, title: String, command: &str, arguments: Option<Vec<serde_json::Value>>) -> CodeLens {
+    CodeLens {
+        range,
+        command: Some(Command {
+            title,
+            command: command.to_string(),
+            arguments,
+        }),
+        data: None,
+    }
+}
+
+/// e.g. "47/47 keys · 31 transparent · reached via MO from _BASE".
+pub fn layer_summary(doc: &DocumentData, layer: &Layer, expected: Option<usize>) -> String {
+    let mut parts = vec![match expected {
+        Some(expected) => format!("{}/{} keys", layer.key_count, expected),
+        None => format!("{} keys", layer.key_count),
+    }];
+
+    let transparent = layer
+        .keys
+        .iter()
+        .filter(|k| doc.keycodes.get(&doc.content[(*k).clone()]).is_some_and(|kc| kc.is_transparent()))
+        .count();
+    if transparent > 0 {
+        parts.push(format!("{} transparent", transparent));
+    }
+
+    let reached: Vec<String> = reached_via(doc, layer)
+        .into_iter()
+        .map(|(via, from)| format!("{} from {}", via, from))
+        .collect();
+    if !reached.is_empty() {
+        parts.push(format!("reached via {}", reached.join(", ")));
+    }
+    parts.join(" · ")
+}
+
+/// The layer keys (`MO`, `LT`, `TG`...) in other layers that lead to `layer`,
+/// as (macro, source layer) pairs.
+fn reached_via<'a>(doc: &'a DocumentData, layer: &Layer) -> Vec<(&'a str, &'a str)> {
+    let mut found = Vec::new();
+    for other in doc.layers.iter().filter(|l| l.name != layer.name) {
+        for key in &other.keys {
+            let KeyExpr::Call { name, args } = parse_key_expr(&doc.content[key.clone()]) else {
+                continue;
+            };
+            let Some(mac) = doc.macros.get(&name) else {
+                continue;
+            };
+            let targets_layer = mac.params.iter().zip(&args).any(|(param, arg)| {
+                matches!(param.kind, ParamKind::Layer { .. })
+                    && matches!(arg, KeyExpr::Ident(a) | KeyExpr::Number(a) if *a == layer.name)
+            });
+            let entry = (mac.name.as_str(), other.name.as_str());
+            if targets_layer && !found.contains(&entry) {
+                found.push(entry);
+            }
+        }
+    }
+    found
+}
+
+/// The layer's legends laid out on the physical layout, one text line per row.
+/// Without a matching layout, keys are listed on a single line.
+pub fn render_preview(doc: &DocumentData, layer: &Layer, layout: Option<&[LayoutKey]>, ctx: &LegendContext) -> String {
+    let legends: Vec<String> = layer
+        .keys
+        .iter()
+        .map(|k| key_legend(&doc.content[k.clone()], ctx))
+        .collect();
+    let mut preview = format!("{} ({})\n", layer.name, layer.macro_name);
+
+    let Some(layout) = layout.filter(|l| l.len() == legends.len()) else {
+        preview.push_str(&legends.join(" | "));
+        return preview;
+    };
+
+    let width = legends.iter().map(|l| l.chars().count()).max().unwrap_or(0).max(1);
+    let mut lines: Vec<String> = Vec::new();
+    for row in layout_rows(layout) {
+        let mut line = String::new();
+        let mut next_slot = 0;
+        for i in row {
+            let slot = (layout[i].x.max(0.0).floor() as usize).max(next_slot);
+            next_slot = slot + 1;
+
+            let column = slot * (width + 1);
+            let used = line.chars().count();
+            line.push_str(&" ".repeat(column.saturating_sub(used)));
+            line.push_str(&format!("{:<width$} ", legends[i], width = width));
+        }
+        lines.push(line);
+    }
+    let lines: Vec<&str> = lines.iter().map(|l| l.trim_end()).collect();
+    preview.push_str(&lines.join("\n"));
+    preview
+}
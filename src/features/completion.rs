@@ -0,0 +1,314 @@
+use crate::document::DocumentData;
+use crate::parser::enums::find_layer_enum;
+use crate::parser::expr::enclosing_call;
+use crate::parser::keycodes::{Keycode, KeycodeMap};
+use crate::parser::macros::{Macro, MacroMap, ModMaskMap, ParamKind};
+use serde_json::json;
+use std::collections::HashMap;
+use tower_lsp::lsp_types::*;
+
+/// Upper bound on the items returned per request; the list is marked incomplete past it.
+pub const MAX_COMPLETIONS: usize = 100;
+
+/// What may be typed at the cursor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompletionContext {
+    /// Top level of a `LAYOUT(...)` argument, or inside a macro not in the database there.
+    Any,
+    /// Outside the keymap layers, e.g. a `case` in `process_record_user`.
+    Code,
+    /// An argument of a known macro.
+    Param(ParamKind),
+    /// Past the last parameter of a known macro.
+    Nothing,
+}
+
+pub fn completion_context(doc: &DocumentData, offset: usize) -> CompletionContext {
+    let in_layers = doc.layers.iter().any(|l| l.span.start < offset && offset <= l.span.end);
+    let fallback = if in_layers { CompletionContext::Any } else { CompletionContext::Code };
+    let Some(call) = enclosing_call(&doc.tokens, offset) else {
+        return fallback;
+    };
+    match doc.macros.get(&call.name) {
+        Some(mac) if !mac.params.is_empty() => match mac.params.get(call.arg_index) {
+            Some(param) => CompletionContext::Param(param.kind.clone()),
+            None => CompletionContext::Nothing,
+        },
+        _ => fallback,
+    }
+}
+
+struct Entry {
+    item: CompletionItem,
+    /// Lower ranks sort first.
+    rank: u8,
+    basic: bool,
+}
+
+/// Completion items for the keycode, macro and mask databases. These only change
+/// with overlays, so the server builds them once instead of on every request.
+pub struct CompletionCache {
+    keycodes: Vec<Entry>,
+    macros: Vec<Entry>,
+    mod_masks: Vec<Entry>,
+}
+
+impl CompletionCache {
+    pub fn new(keycodes: &KeycodeMap, macros: &MacroMap, mod_masks: &ModMaskMap) -> Self {
+        let keycodes = keycodes
+            .iter()
+            .map(|(name, kc)| Entry {
+                item: CompletionItem {
+                    label: name.clone(),
+                    kind: Some(CompletionItemKind::CONSTANT),
+                    detail: Some(kc.description.clone()),
+                    tags: kc.deprecated.as_ref().map(|_| vec![CompletionItemTag::DEPRECATED]),
+                    data: Some(json!({ "source": "keycode" })),
+                    ..Default::default()
+                },
+                rank: keycode_rank(kc),
+                basic: kc.is_basic(),
+            })
+            .collect();
+
+        let macros = macros
+            .iter()
+            .map(|(name, mac)| Entry {
+                item: CompletionItem {
+                    label: name.clone(),
+                    kind: Some(CompletionItemKind::FUNCTION),
+                    detail: Some(mac.detail.clone()),
+                    insert_text: Some(format!("{}(", name)),
+                    insert_text_format: Some(InsertTextFormat::PLAIN_TEXT),
+                    data: Some(json!({ "source": "macro" })),
+                    ..Default::default()
+                },
+                // Keycode accessors belong in process_record_user, not in layers.
+                rank: if name.starts_with("QK_") { 5 } else { 3 },
+                basic: false,
+            })
+            .collect();
+
+        let mod_masks = mod_masks
+            .iter()
+            .map(|(name, mask)| Entry {
+                item: CompletionItem {
+                    label: name.clone(),
+                    kind: Some(CompletionItemKind::CONSTANT),
+                    detail: Some(mask.description.clone()),
+                    data: Some(json!({ "source": "mod_mask" })),
+                    ..Default::default()
+                },
+                rank: 0,
+                basic: false,
+            })
+            .collect();
+
+        CompletionCache { keycodes, macros, mod_masks }
+    }
+}
+
+fn keycode_rank(kc: &Keycode) -> u8 {
+    if kc.deprecated.is_some() {
+        return 9;
+    }
+    match kc.category.as_str() {
+        "alpha" | "function" | "punctuation" | "modifier" | "command" => 2,
+        "shifted" | "media" | "locking" | "special" => 3,
+        _ => 4,
+    }
+}
+
+/// The identifier being typed, i.e. the identifier characters right before `offset`.
+pub fn typed_prefix(content: &str, offset: usize) -> &str {
+    let before = &content[..offset];
+    let start = before
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| c.is_alphanumeric() || *c == '_')
+        .last()
+        .map_or(offset, |(i, _)| i);
+    &content[start..offset]
+}
+
+fn matches_prefix(label: &str, prefix: &str) -> bool {
+    if prefix.is_empty() {
+        return true;
+    }
+    let label = label.to_ascii_uppercase();
+    let prefix = prefix.to_ascii_uppercase();
+    label.starts_with(&prefix) || label.strip_prefix("KC_").is_some_and(|rest| rest.starts_with(&prefix))
+}
+
+/// Snippet inserting a call with one tab stop per parameter, e.g. `LT(${1:layer}, ${2:KC_})`.
+/// Layer parameters offer the given layers as choices.
+pub fn macro_snippet(mac: &Macro, layers: &[&str]) -> String {
+    if mac.params.is_empty() {
+        return format!("{}($1)", mac.name);
+    }
+    let stops: Vec<String> = mac
+        .params
+        .iter()
+        .enumerate()
+        .map(|(i, param)| match param.kind {
+            ParamKind::Layer { .. } if !layers.is_empty() => format!("${{{}|{}|}}", i + 1, layers.join(",")),
+            _ => {
+                let placeholder = param.placeholder.as_deref().unwrap_or(&param.name);
+                format!("${{{}:{}}}", i + 1, escape_snippet(placeholder))
+            }
+        })
+        .collect();
+    format!("{}({})", mac.name, stops.join(", "))
+}
+
+fn escape_snippet(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('$', "\\$").replace('}', "\\}")
+}
+
+pub fn completion_list(
+    uri: &Url,
+    doc: &DocumentData,
+    cache: &CompletionCache,
+    usage: &HashMap<String, usize>,
+    offset: usize,
+    snippets: bool,
+) -> CompletionList {
+    let mut doc_items = Vec::new();
+    let mut candidates: Vec<&Entry> = Vec::new();
+    let prefix = typed_prefix(&doc.content, offset);
+
+    match completion_context(doc, offset) {
+        CompletionContext::Any => {
+            candidates.extend(&cache.keycodes);
+            candidates.extend(&cache.macros);
+            layer_items(doc, &mut doc_items);
+            custom_keycode_items(doc, &mut doc_items);
+        }
+        // Plain C code: only keycodes and layers, and only once the user starts typing one.
+        CompletionContext::Code if !prefix.is_empty() => {
+            candidates.extend(&cache.keycodes);
+            layer_items(doc, &mut doc_items);
+            custom_keycode_items(doc, &mut doc_items);
+        }
+        CompletionContext::Param(ParamKind::Layer { .. }) => layer_items(doc, &mut doc_items),
+        CompletionContext::Param(ParamKind::BasicKeycode) => {
+            candidates.extend(cache.keycodes.iter().filter(|e| e.basic));
+        }
+        CompletionContext::Param(ParamKind::Keycode) => {
+            candidates.extend(&cache.keycodes);
+            candidates.extend(&cache.macros);
+            custom_keycode_items(doc, &mut doc_items);
+        }
+        CompletionContext::Param(ParamKind::ModMask) => candidates.extend(&cache.mod_masks),
+        CompletionContext::Param(ParamKind::TapDance) => tap_dance_items(doc, &mut doc_items),
+        CompletionContext::Param(ParamKind::Int { .. }) | CompletionContext::Code | CompletionContext::Nothing => {}
+    }
+
+    let mut ranked: Vec<(u8, usize, CompletionItem)> = doc_items
+        .into_iter()
+        .filter(|(_, item)| matches_prefix(&item.label, prefix))
+        .map(|(rank, item)| (rank, usage.get(&item.label).copied().unwrap_or(0), item))
+        .collect();
+    ranked.extend(
+        candidates
+            .into_iter()
+            .filter(|e| matches_prefix(&e.item.label, prefix))
+            .map(|e| (e.rank, usage.get(&e.item.label).copied().unwrap_or(0), e.item.clone())),
+    );
+    ranked.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)).then_with(|| a.2.label.cmp(&b.2.label)));
+
+    let layers = layer_names(doc);
+
+    let is_incomplete = ranked.len() > MAX_COMPLETIONS;
+    let items = ranked
+        .into_iter()
+        .take(MAX_COMPLETIONS)
+        .enumerate()
+        .map(|(i, (_, _, mut item))| {
+            item.sort_text = Some(format!("{:04}", i));
+            item.filter_text = Some(item.label.clone());
+            if let Some(data) = item.data.as_mut().and_then(|d| d.as_object_mut()) {
+                data.insert("uri".to_string(), json!(uri));
+            }
+            if snippets
+                && item.kind == Some(CompletionItemKind::FUNCTION)
+                && let Some(mac) = doc.macros.get(&item.label)
+            {
+                item.insert_text = Some(macro_snippet(mac, &layers));
+                item.insert_text_format = Some(InsertTextFormat::SNIPPET);
+            }
+            item
+        })
+        .collect();
+
+    CompletionList { is_incomplete, items }
+}
+
+/// Fills in the documentation left out of `completion_list` items.
+pub fn resolve(mut item: CompletionItem, doc: Option<&DocumentData>, keycodes: &KeycodeMap, macros: &MacroMap, mod_masks: &ModMaskMap) -> CompletionItem {
+    let source = item.data.as_ref().and_then(|d| d.get("source")).and_then(|s| s.as_str());
+    let keycodes = doc.map_or(keycodes, |d| &d.keycodes);
+    let macros = doc.map_or(macros, |d| &d.macros);
+
+    let doc_text = match source {
+        Some("keycode") => keycodes.get(&item.label).map(|kc| kc.doc.clone()),
+        Some("macro") => macros.get(&item.label).map(|mac| mac.doc.clone()),
+        Some("mod_mask") => mod_masks.get(&item.label).map(|mask| mask.doc.clone()),
+        _ => None,
+    };
+    if let Some(doc_text) = doc_text {
+        item.documentation = Some(Documentation::String(doc_text));
+    }
+    item
+}
+
+/// Layers of the keymap: the members of its layer enum, in order, then any other
+/// names used as keymaps designators.
+fn layer_names(doc: &DocumentData) -> Vec<&str> {
+    let designators: Vec<&str> = doc.layers.iter().map(|l| l.name.as_str()).collect();
+    let members = find_layer_enum(&doc.enums, &designators)
+        .into_iter()
+        .flat_map(|e| &e.members)
+        .map(|m| m.name.as_str());
+
+    let mut names: Vec<&str> = Vec::new();
+    for name in members.chain(designators.iter().copied()) {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+fn layer_items(doc: &DocumentData, items: &mut Vec<(u8, CompletionItem)>) {
+    for name in layer_names(doc) {
+        items.push((0, CompletionItem {
+            label: name.to_string(),
+            kind: Some(CompletionItemKind::ENUM_MEMBER),
+            detail: Some("Layer".to_string()),
+            ..Default::default()
+        }));
+    }
+}
+
+fn custom_keycode_items(doc: &DocumentData, items: &mut Vec<(u8, CompletionItem)>) {
+    for custom in &doc.custom_keycodes {
+        items.push((1, CompletionItem {
+            label: custom.name.clone(),
+            kind: Some(CompletionItemKind::ENUM_MEMBER),
+            detail: Some("Custom Keycode".to_string()),
+            ..Default::default()
+        }));
+    }
+}
+
+fn tap_dance_items(doc: &DocumentData, items: &mut Vec<(u8, CompletionItem)>) {
+    for tap_dance in doc.tap_dances.iter().flat_map(|t| &t.entries) {
+        items.push((0, CompletionItem {
+            label: tap_dance.name.clone(),
+            kind: Some(CompletionItemKind::ENUM_MEMBER),
+            detail: Some("Tap Dance".to_string()),
+            ..Default::default()
+        }));
+    }
+}
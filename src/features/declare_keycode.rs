@@ -0,0 +1,234 @@
+use crate::features::definition::function_bodies;
+use crate::features::references::{user_symbol_names, SourceFile};
+use crate::features::typos::looks_like_keycode;
+use crate::parser::custom::is_custom_keycode_enum;
+use crate::parser::defines::parse_defines;
+use crate::parser::enums::parse_enums;
+use crate::parser::expr::enclosing_call;
+use crate::parser::layers::{keymaps_declaration_span, Layer};
+use crate::parser::macros::{MacroMap, ParamKind};
+use crate::parser::tokens::Token;
+use std::collections::HashMap;
+use std::ops::Range;
+use tower_lsp::lsp_types::*;
+
+/// Indentation of generated code, following QMK's own keymaps.
+const INDENT: &str = "    ";
+
+/// Prefixes of QMK's keycode families and of the `keymap_extras` locale headers.
+/// Names starting with one are QMK's, even when the bundled database lacks them.
+const QMK_PREFIXES: &[&str] = &[
+    "QK_", "MS_", "OS_", "RGB_", "UG_", "RM_", "BL_", "AU_", "MU_", "MI_", "HF_", "CK_", "DT_", "DM_", "SH_", "CW_",
+    "AS_", "NK_", "MAGIC_", "SQ_", "JS_", "PB_", "TL_", "AC_", "EC_", "OU_", "KO_", "DB_", "VK_", "CL_", "AG_", "CG_",
+    "GU_", "BS_", "STN_", "BE_", "BP_", "BR_", "CA_", "CH_", "CM_", "CZ_", "DE_", "DK_", "DP_", "DV_", "EE_", "ES_",
+    "FI_", "FR_", "GR_", "HR_", "HU_", "IE_", "IL_", "IS_", "IT_", "JP_", "KR_", "LT_", "LV_", "NE_", "NL_", "NO_",
+    "PL_", "PT_", "RO_", "RS_", "RU_", "SE_", "SI_", "SK_", "TR_", "UA_", "UK_", "US_", "WK_",
+];
+
+/// Whether `name` is spelled like a custom keycode: upper case, digits and
+/// underscores, without the `KC_` prefix QMK keeps for its own keycodes.
+pub fn looks_like_custom_keycode(name: &str) -> bool {
+    name.len() > 1
+        && !looks_like_keycode(name)
+        && name.starts_with(|c: char| c.is_ascii_uppercase())
+        && name.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// Whether `name` starts like one of QMK's own keycodes (`QK_REP`, `MS_BTN1`, `DE_Z`).
+pub fn has_qmk_prefix(name: &str) -> bool {
+    QMK_PREFIXES.iter().any(|prefix| name.starts_with(prefix))
+}
+
+/// The identifier written as a key (or a keycode argument of a macro) in one of
+/// `layers` within `range`, if it is spelled like a custom keycode. Arguments
+/// taking anything but a full keycode, such as the basic keycode of `LT(1, kc)`,
+/// can't hold a custom keycode.
+pub fn keycode_candidate(tokens: &[Token], layers: &[Layer], macros: &MacroMap, range: Range<usize>) -> Option<String> {
+    let in_range = |span: &Range<usize>| span.start <= range.end && range.start <= span.end;
+    let keys: Vec<&Range<usize>> = layers.iter().flat_map(|l| &l.keys).filter(|k| in_range(k)).collect();
+
+    tokens.iter().enumerate().find_map(|(i, token)| {
+        let is_key = token.is_ident()
+            && in_range(&token.span)
+            && keys.iter().any(|k| k.start <= token.span.start && token.span.end <= k.end)
+            && !tokens.get(i + 1).is_some_and(|t| t.is_punct('('));
+        let takes_keycode = || {
+            let Some(call) = enclosing_call(tokens, token.span.start) else {
+                return true;
+            };
+            match macros.get(&call.name).and_then(|mac| mac.params.get(call.arg_index)) {
+                Some(param) => param.kind == ParamKind::Keycode,
+                None => true,
+            }
+        };
+        (is_key && looks_like_custom_keycode(&token.text) && takes_keycode()).then(|| token.text.clone())
+    })
+}
+
+/// Declares `name` as a custom keycode: adds it to `enum custom_keycodes` (creating
+/// the enum, starting at `SAFE_RANGE`, above `keymaps` or else below the includes if
+/// there is none) and adds a `case` for it to `process_record_user` (creating that
+/// function at the end of the keymap if needed). `files[0]` is the keymap; the enum and the function may live
+/// in any of the files. Returns nothing if the name is already declared in `files`.
+pub fn code_action(files: &[SourceFile], name: &str) -> Option<CodeActionOrCommand> {
+    let doc = files.first()?;
+    if is_declared(files, name) {
+        return None;
+    }
+
+    let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+    let (file, edit) = enum_edit(files, name).or_else(|| {
+        let declaration = format!("enum custom_keycodes {{\n{}{} = SAFE_RANGE,\n}};\n", INDENT, name);
+        // `SAFE_RANGE` comes from `QMK_KEYBOARD_H`, so without a `keymaps` array to
+        // go above, the enum goes below the includes.
+        let (at, text) = match (keymaps_declaration_span(&doc.content), includes_end(&doc.content)) {
+            (Some(keymaps), _) => (keymaps.start, format!("{}\n", declaration)),
+            (None, Some(end)) => {
+                let before = if doc.content[..end].ends_with('\n') { "\n" } else { "\n\n" };
+                let after = if doc.content[end..].starts_with('\n') || end == doc.content.len() { "" } else { "\n" };
+                (end, format!("{}{}{}", before, declaration, after))
+            }
+            (None, None) => (0, format!("{}\n", declaration)),
+        };
+        Some((doc, TextEdit::new(doc.line_index.range(&doc.content, at..at), text)))
+    })?;
+    changes.entry(file.uri.clone()).or_default().push(edit);
+
+    let (file, edit) = handler_edit(files, name).or_else(|| {
+        let at = doc.content.len();
+        let newline = if doc.content.is_empty() || doc.content.ends_with('\n') { "" } else { "\n" };
+        let text = format!(
+            "{}\nbool process_record_user(uint16_t keycode, keyrecord_t *record) {{\n{}switch (keycode) {{{}\n{}}}\n{}return true;\n}}\n",
+            newline,
+            INDENT,
+            case_stub(name, &INDENT.repeat(2)),
+            INDENT,
+            INDENT
+        );
+        Some((doc, TextEdit::new(doc.line_index.range(&doc.content, at..at), text)))
+    })?;
+    changes.entry(file.uri.clone()).or_default().push(edit);
+
+    Some(CodeActionOrCommand::CodeAction(CodeAction {
+        title: format!("Declare custom keycode '{}'", name),
+        kind: Some(CodeActionKind::QUICKFIX),
+        edit: Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }))
+}
+
+/// The end of the last `#include` line in `content`, after its line break.
+fn includes_end(content: &str) -> Option<usize> {
+    let mut offset = 0;
+    let mut end = None;
+    for line in content.split_inclusive('\n') {
+        offset += line.len();
+        let directive = line.trim_start().strip_prefix('#');
+        if directive.is_some_and(|d| d.trim_start().starts_with("include")) {
+            end = Some(offset);
+        }
+    }
+    end
+}
+
+/// Whether any of `files` declares `name`: a layer, enum member or `#define`.
+fn is_declared(files: &[SourceFile], name: &str) -> bool {
+    user_symbol_names(files).contains_key(name)
+        || files.iter().any(|file| {
+            parse_enums(&file.tokens).iter().any(|e| e.member(name).is_some())
+                || parse_defines(&file.content).iter().any(|d| d.name == name)
+        })
+}
+
+/// Appends `name` to the first custom keycode enum in `files`, matching its layout.
+fn enum_edit<'a>(files: &'a [SourceFile], name: &str) -> Option<(&'a SourceFile, TextEdit)> {
+    files.iter().find_map(|file| {
+        let enums = parse_enums(&file.tokens);
+        let custom = enums.iter().find(|e| is_custom_keycode_enum(e))?;
+        let content = &file.content;
+        let close = custom.span.end - 1;
+        let last = custom.members.last();
+
+        // The last token before the closing brace: a trailing comma or the end of the last member.
+        let before_close = file.tokens.iter().rev().find(|t| t.span.end <= close && t.span.start > custom.name_span.start)?;
+        let multiline = content[custom.name_span.end..close].contains('\n');
+        let text = match (last, multiline) {
+            (None, _) => format!("{} = SAFE_RANGE", name),
+            (Some(member), true) => {
+                let indent = line_indent(content, member.span.start);
+                match before_close.is_punct(',') {
+                    true => format!("\n{}{},", indent, name),
+                    false => format!(",\n{}{}", indent, name),
+                }
+            }
+            (Some(_), false) => match before_close.is_punct(',') {
+                true => format!(" {},", name),
+                false => format!(", {}", name),
+            },
+        };
+        let at = before_close.span.end;
+        Some((file, TextEdit::new(file.line_index.range(content, at..at), text)))
+    })
+}
+
+/// Adds a `case` for `name` to the `switch (keycode)` in `process_record_user`, or
+/// such a `switch` if the function has none.
+fn handler_edit<'a>(files: &'a [SourceFile], name: &str) -> Option<(&'a SourceFile, TextEdit)> {
+    files.iter().find_map(|file| {
+        let tokens = &file.tokens;
+        let body = function_bodies(tokens, |name| name == "process_record_user").next()?;
+        let content = &file.content;
+        let open = &tokens[body.start];
+
+        let switch = (body.start..body.end.saturating_sub(4)).find_map(|i| {
+            let t = &tokens[i..i + 5];
+            let is_switch = t[0].text == "switch"
+                && t[1].is_punct('(')
+                && t[2].text == "keycode"
+                && t[3].is_punct(')')
+                && t[4].is_punct('{');
+            is_switch.then_some((i, i + 4))
+        });
+        let (at, text) = match switch {
+            Some((switch, switch_open)) => {
+                let switch_indent = line_indent(content, tokens[switch].span.start);
+                let indent = (switch_open..body.end)
+                    .find(|&j| tokens[j].text == "case")
+                    .map(|j| line_indent(content, tokens[j].span.start).to_string())
+                    .unwrap_or_else(|| format!("{}{}", switch_indent, INDENT));
+                (tokens[switch_open].span.end, case_stub(name, &indent))
+            }
+            None => {
+                let indent = format!("{}{}", line_indent(content, open.span.start), INDENT);
+                let text = format!(
+                    "\n{}switch (keycode) {{{}\n{}}}",
+                    indent,
+                    case_stub(name, &format!("{}{}", indent, INDENT)),
+                    indent
+                );
+                (open.span.end, text)
+            }
+        };
+        Some((file, TextEdit::new(file.line_index.range(content, at..at), text)))
+    })
+}
+
+/// `case NAME:` handling presses of the key, on new lines indented by `indent`.
+fn case_stub(name: &str, indent: &str) -> String {
+    format!(
+        "\n{indent}case {name}:\n{indent}{unit}if (record->event.pressed) {{\n{indent}{unit}{unit}// {name} pressed\n{indent}{unit}}}\n{indent}{unit}return false;",
+        indent = indent,
+        unit = INDENT,
+        name = name
+    )
+}
+
+/// Leading whitespace of the line containing `offset`.
+fn line_indent(content: &str, offset: usize) -> &str {
+    let start = content[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line = &content[start..];
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
@@ -0,0 +1,109 @@
+use crate::features::references::{find_symbol, SourceFile, UserSymbolKind};
+use crate::parser::defines::parse_defines;
+use crate::parser::layers::parse_layers;
+use crate::parser::tokens::{token_at, Token};
+use tower_lsp::lsp_types::Location;
+
+/// Declaration of the identifier under `offset` in the first file: a layer's
+/// `[_NAV] = LAYOUT(` block, a custom keycode's enum member, or a `#define`.
+/// From the layer block itself, jumps on to the layer's enum member.
+pub fn definition(files: &[SourceFile], offset: usize) -> Option<Location> {
+    let doc = files.first()?;
+    let token = ident_at(doc, offset)?;
+    let name = token.text.as_str();
+
+    if let Some(symbol) = find_symbol(name, files) {
+        if symbol.kind == UserSymbolKind::Layer {
+            let block = files.iter().find_map(|file| {
+                let layer = parse_layers(&file.content).into_iter().find(|l| l.name == name)?;
+                Some((file, layer.name_span))
+            });
+            if let Some((file, span)) = block {
+                let on_block = std::ptr::eq(file, doc) && span == token.span;
+                if !on_block {
+                    return Some(file.location(span));
+                }
+            }
+        }
+        if let Some((i, span)) = symbol.declaration {
+            return Some(files[i].location(span));
+        }
+    }
+
+    files.iter().find_map(|file| {
+        let define = parse_defines(&file.content).into_iter().find(|d| d.name == name)?;
+        Some(file.location(define.name_span))
+    })
+}
+
+/// The `case` label handling the custom keycode under `offset` in a
+/// `process_record_*` function (`process_record_user`, or a userspace
+/// `process_record_keymap`).
+pub fn implementation(files: &[SourceFile], offset: usize) -> Option<Location> {
+    let doc = files.first()?;
+    let name = ident_at(doc, offset)?.text.as_str();
+    if find_symbol(name, files)?.kind != UserSymbolKind::CustomKeycode {
+        return None;
+    }
+
+    files.iter().find_map(|file| {
+        let tokens = &file.tokens;
+        let span = function_bodies(tokens, |name| name.starts_with("process_record_")).find_map(|body| {
+            tokens[body].windows(3).find_map(|w| {
+                let is_case = w[0].text == "case" && w[1].text == name && w[2].is_punct(':');
+                is_case.then(|| w[0].span.start..w[2].span.end)
+            })
+        })?;
+        Some(file.location(span))
+    })
+}
+
+fn ident_at(file: &SourceFile, offset: usize) -> Option<&Token> {
+    let token = &file.tokens[token_at(&file.tokens, offset)?];
+    token.is_ident().then_some(token)
+}
+
+/// Token ranges of the bodies of functions whose name satisfies `matches`.
+pub fn function_bodies<'a>(
+    tokens: &'a [Token],
+    matches: impl Fn(&str) -> bool + 'a,
+) -> impl Iterator<Item = std::ops::Range<usize>> + 'a {
+    (0..tokens.len()).filter_map(move |i| {
+        let name = &tokens[i];
+        if !(name.is_ident() && matches(&name.text) && tokens.get(i + 1)?.is_punct('(')) {
+            return None;
+        }
+        // Skip the parameter list; a prototype ends in `;` instead of a body.
+        let mut j = i + 1;
+        let mut depth = 0;
+        loop {
+            let t = tokens.get(j)?;
+            if t.is_punct('(') {
+                depth += 1;
+            } else if t.is_punct(')') {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+            j += 1;
+        }
+        if !tokens.get(j + 1)?.is_punct('{') {
+            return None;
+        }
+
+        let open = j + 1;
+        let mut depth = 0;
+        for (k, t) in tokens.iter().enumerate().skip(open) {
+            if t.is_punct('{') {
+                depth += 1;
+            } else if t.is_punct('}') {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(open..k + 1);
+                }
+            }
+        }
+        Some(open..tokens.len())
+    })
+}
@@ -0,0 +1,46 @@
+use crate::document::DocumentData;
+use crate::parser::defines::parse_defines;
+use crate::parser::tokens::token_at;
+use tower_lsp::lsp_types::*;
+
+/// Every occurrence in the document of the identifier at `offset`. Layer and
+/// custom keycode definitions (enum members, `#define`s and `keymaps`
+/// designators) are marked `WRITE`, all other occurrences `READ`. Comments and
+/// strings never match since they aren't tokenized.
+pub fn document_highlights(doc: &DocumentData, offset: usize) -> Option<Vec<DocumentHighlight>> {
+    let token = &doc.tokens[token_at(&doc.tokens, offset)?];
+    if !token.is_ident() {
+        return None;
+    }
+    let name = token.text.as_str();
+
+    let mut definitions: Vec<usize> = doc
+        .enums
+        .iter()
+        .flat_map(|e| &e.members)
+        .filter(|m| m.name == name)
+        .map(|m| m.span.start)
+        .collect();
+    definitions.extend(
+        parse_defines(&doc.content)
+            .into_iter()
+            .filter(|d| d.name == name)
+            .map(|d| d.name_span.start),
+    );
+    definitions.extend(doc.layers.iter().filter(|l| l.name == name).map(|l| l.name_span.start));
+
+    let highlights = doc
+        .tokens
+        .iter()
+        .filter(|t| t.is_ident() && t.text == name)
+        .map(|t| DocumentHighlight {
+            range: doc.line_index.range(&doc.content, t.span.clone()),
+            kind: Some(if definitions.contains(&t.span.start) {
+                DocumentHighlightKind::WRITE
+            } else {
+                DocumentHighlightKind::READ
+            }),
+        })
+        .collect();
+    Some(highlights)
+}
@@ -0,0 +1,41 @@
+use crate::document::DocumentData;
+use crate::parser::defines::parse_conditional_branches;
+use crate::parser::layers::keymaps_span;
+use std::ops::Range;
+use tower_lsp::lsp_types::*;
+
+/// Folds for the `keymaps` array and each layer in it, enums, tap dance and
+/// combo tables, and every branch of an `#if` block.
+pub fn folding_ranges(doc: &DocumentData) -> Vec<FoldingRange> {
+    let mut spans: Vec<Range<usize>> = Vec::new();
+    spans.extend(keymaps_span(&doc.content));
+    spans.extend(doc.layers.iter().map(|l| l.span.clone()));
+    spans.extend(doc.enums.iter().map(|e| e.span.clone()));
+    spans.extend([&doc.tap_dances, &doc.combos].into_iter().flatten().map(|t| t.span.clone()));
+
+    // Keep the line with the closing brace or parenthesis visible.
+    let mut ranges: Vec<FoldingRange> = spans
+        .into_iter()
+        .filter(|span| !span.is_empty())
+        .filter_map(|span| fold(doc, span.start, span.end - 1, None))
+        .collect();
+    ranges.extend(
+        parse_conditional_branches(&doc.content)
+            .into_iter()
+            .filter_map(|branch| fold(doc, branch.start, branch.end, Some(FoldingRangeKind::Region))),
+    );
+    ranges.sort_by_key(|r| (r.start_line, std::cmp::Reverse(r.end_line)));
+    ranges
+}
+
+/// Folds from the line of `start` up to the line before that of `end`, if that spans anything.
+fn fold(doc: &DocumentData, start: usize, end: usize, kind: Option<FoldingRangeKind>) -> Option<FoldingRange> {
+    let start_line = doc.line_index.position(&doc.content, start).line;
+    let end_line = doc.line_index.position(&doc.content, end).line.checked_sub(1)?;
+    (end_line > start_line).then(|| FoldingRange {
+        start_line,
+        end_line,
+        kind,
+        ..Default::default()
+    })
+}
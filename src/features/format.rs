@@ -0,0 +1,267 @@
+use crate::document::DocumentData;
+use crate::parser::info_json::{key_rows, KeyboardInfo, LayoutKey};
+use crate::parser::layers::Layer;
+use std::ops::Range;
+use tower_lsp::lsp_types::*;
+
+/// Width of the empty column standing in for a gap in the physical layout,
+/// such as between split halves.
+const GAP_WIDTH: usize = 2;
+
+/// Re-flows every `LAYOUT(...)` overlapping `range` (or all of them) into a grid
+/// following its info.json layout. Layers whose macro isn't in info.json, or
+/// whose key count doesn't match it, are left alone.
+pub fn format_layers(
+    doc: &DocumentData,
+    info: &KeyboardInfo,
+    options: &FormattingOptions,
+    range: Option<Range<usize>>,
+) -> Vec<TextEdit> {
+    let unit = if options.insert_spaces {
+        " ".repeat(options.tab_size as usize)
+    } else {
+        "\t".to_string()
+    };
+
+    let mut edits = Vec::new();
+    for layer in &doc.layers {
+        if let Some(range) = &range
+            && (layer.span.end < range.start || layer.span.start > range.end)
+        {
+            continue;
+        }
+        let Some(layout) = info.layouts.get(&layer.macro_name) else {
+            continue;
+        };
+        let Some((span, text)) = format_layer(&doc.content, layer, &layout.layout, &unit) else {
+            continue;
+        };
+        if doc.content[span.clone()] != text {
+            edits.push(TextEdit::new(doc.line_index.range(&doc.content, span), text));
+        }
+    }
+    edits
+}
+
+/// The span of a layer's parenthesized key list and its formatted replacement.
+pub fn format_layer(content: &str, layer: &Layer, layout: &[LayoutKey], unit: &str) -> Option<(Range<usize>, String)> {
+    let keys = &layer.keys;
+    if keys.is_empty() || keys.len() != layout.len() {
+        return None;
+    }
+    let macro_end = layer.span.start + content[layer.span.clone()].find(&layer.macro_name)? + layer.macro_name.len();
+    let open = macro_end + content[macro_end..].find('(')?;
+    let close = layer.span.end - 1;
+    if !content[close..].starts_with(')') {
+        return None;
+    }
+    let key_texts: Vec<&str> = keys.iter().map(|k| &content[k.clone()]).collect();
+    // Keys spanning lines can't sit in a grid cell.
+    if key_texts.iter().any(|k| k.contains('\n')) {
+        return None;
+    }
+
+    // Comments in the gap before each key, and after the last one.
+    let mut gaps = Vec::new();
+    let mut gap_start = open + 1;
+    for key in keys {
+        gaps.push(gap_comments(&content[gap_start..key.start]));
+        gap_start = key.end;
+    }
+    gaps.push(gap_comments(&content[gap_start..close]));
+
+    // Keys can't be reordered, so each line is a run of consecutive keys on one
+    // physical row; layouts listing split halves one after the other go half by half.
+    let physical = key_rows(layout);
+    let mut rows: Vec<usize> = Vec::with_capacity(physical.len());
+    for (i, row) in physical.iter().enumerate() {
+        let line = match rows.last() {
+            None => 0,
+            Some(&last) if physical[i - 1] == *row => last,
+            Some(&last) => last + 1,
+        };
+        rows.push(line);
+    }
+    let row_count = rows.last().map_or(0, |r| r + 1);
+    let columns = key_columns(layout, &rows);
+    let column_count = columns.iter().map(|c| c.index + 1).max().unwrap_or(0);
+
+    // Inline comments stay in front of their key; the rest move to the row's
+    // end (trailing) or onto their own line above the row.
+    let mut head = Vec::new();
+    let mut cells: Vec<String> = Vec::new();
+    let mut trailing: Vec<Vec<String>> = vec![Vec::new(); row_count];
+    let mut above: Vec<Vec<String>> = vec![Vec::new(); row_count];
+    let mut below = Vec::new();
+    for (i, gap) in gaps.into_iter().enumerate() {
+        let mut inline = Vec::new();
+        for comment in gap {
+            let ends_line = comment.newline_after || comment.text.starts_with("//") || i == keys.len();
+            if !comment.newline_before && ends_line {
+                match i {
+                    0 => head.push(comment.text),
+                    _ => trailing[rows[i - 1]].push(comment.text),
+                }
+            } else if !ends_line {
+                inline.push(comment.text);
+            } else if i < keys.len() {
+                above[rows[i]].push(comment.text);
+            } else {
+                below.push(comment.text);
+            }
+        }
+        if let Some(key) = key_texts.get(i) {
+            inline.push(key.to_string());
+            let comma = if i + 1 < keys.len() { "," } else { "" };
+            cells.push(format!("{}{}", inline.join(" "), comma));
+        }
+    }
+
+    let mut widths = vec![0; column_count];
+    for (cell, column) in cells.iter().zip(&columns) {
+        widths[column.index] = widths[column.index].max(cell.chars().count());
+    }
+    for column in columns.iter().filter(|c| c.gap_before) {
+        widths[column.index - 1] = GAP_WIDTH;
+    }
+
+    let line_start = content[..layer.span.start].rfind('\n').map_or(0, |i| i + 1);
+    let outer: String = content[line_start..]
+        .chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .collect();
+    let inner = format!("{}{}", outer, unit);
+
+    let mut text = String::from("(");
+    for comment in sort_comments(head) {
+        text.push(' ');
+        text.push_str(&comment);
+    }
+    text.push('\n');
+    for row in 0..row_count {
+        for comment in &above[row] {
+            text.push_str(&format!("{}{}\n", inner, comment));
+        }
+        let mut line = inner.clone();
+        let mut next_column = 0;
+        for (cell, column) in cells.iter().zip(&columns).filter(|(_, c)| c.row == row) {
+            for width in &widths[next_column..column.index] {
+                line.push_str(&" ".repeat(width + 1));
+            }
+            line.push_str(&format!("{:<width$} ", cell, width = widths[column.index]));
+            next_column = column.index + 1;
+        }
+        let mut line = line.trim_end().to_string();
+        for comment in sort_comments(std::mem::take(&mut trailing[row])) {
+            line.push(' ');
+            line.push_str(&comment);
+        }
+        text.push_str(&line);
+        text.push('\n');
+    }
+    for comment in below {
+        text.push_str(&format!("{}{}\n", inner, comment));
+    }
+    text.push_str(&outer);
+    text.push(')');
+
+    Some((open..close + 1, text))
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Column {
+    row: usize,
+    index: usize,
+    /// Whether an empty gap column sits right before this one.
+    gap_before: bool,
+}
+
+/// Grid column of every key. Keys snap to whole units of `x`; units no key in
+/// any row covers (counting its width `w`) collapse into a single gap column.
+fn key_columns(layout: &[LayoutKey], rows: &[usize]) -> Vec<Column> {
+    let mut slots: Vec<usize> = Vec::with_capacity(layout.len());
+    for (i, key) in layout.iter().enumerate() {
+        let mut slot = key.x.max(0.0).floor() as usize;
+        if i > 0 && rows[i] == rows[i - 1] {
+            slot = slot.max(slots[i - 1] + 1);
+        }
+        slots.push(slot);
+    }
+
+    let end = layout
+        .iter()
+        .zip(&slots)
+        .map(|(key, slot)| slot + key.w.unwrap_or(1.0).round().max(1.0) as usize)
+        .max()
+        .unwrap_or(0);
+    let mut covered = vec![false; end];
+    for (key, slot) in layout.iter().zip(&slots) {
+        let width = key.w.unwrap_or(1.0).round().max(1.0) as usize;
+        covered[*slot..slot + width].fill(true);
+    }
+
+    // Map every slot a key starts in to a column, inserting one gap column per run of uncovered slots.
+    let mut mapping = Vec::new();
+    let mut index = 0;
+    let mut gap = false;
+    for (slot, is_covered) in covered.iter().enumerate() {
+        if !is_covered {
+            gap = index > 0;
+            continue;
+        }
+        if !slots.contains(&slot) {
+            continue;
+        }
+        if gap {
+            index += 1;
+        }
+        mapping.push((slot, index, gap));
+        gap = false;
+        index += 1;
+    }
+
+    slots
+        .iter()
+        .zip(rows)
+        .map(|(slot, row)| {
+            let (_, index, gap_before) = mapping.iter().find(|(s, _, _)| s == slot).copied().unwrap();
+            Column { row: *row, index, gap_before }
+        })
+        .collect()
+}
+
+struct Comment {
+    text: String,
+    newline_before: bool,
+    newline_after: bool,
+}
+
+/// Comments in the text between two keys (or a key and a parenthesis).
+fn gap_comments(gap: &str) -> Vec<Comment> {
+    let mut comments = Vec::new();
+    let mut i = 0;
+    while i < gap.len() {
+        let rest = &gap[i..];
+        let len = if rest.starts_with("//") {
+            rest.find('\n').unwrap_or(rest.len())
+        } else if rest.starts_with("/*") {
+            rest.find("*/").map_or(rest.len(), |end| end + 2)
+        } else {
+            i += rest.chars().next().map_or(1, char::len_utf8);
+            continue;
+        };
+        comments.push(Comment {
+            text: rest[..len].trim_end().to_string(),
+            newline_before: gap[..i].contains('\n'),
+            newline_after: rest[len..].contains('\n'),
+        });
+        i += len;
+    }
+    comments
+}
+
+/// Puts block comments before line comments, which would swallow anything after them.
+fn sort_comments(mut comments: Vec<String>) -> Vec<String> {
+    comments.sort_by_key(|c| c.starts_with("//"));
+    comments
+}
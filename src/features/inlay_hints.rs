@@ -0,0 +1,128 @@
+use crate::config::InlayHintStyle;
+use crate::document::DocumentData;
+use crate::parser::info_json::{key_rows, KeyboardInfo, LayoutKey};
+use std::collections::HashMap;
+use std::ops::Range;
+use tower_lsp::lsp_types::*;
+
+/// A hint before every key of the layers overlapping `range`, plus a row marker
+/// wherever the matching info.json layout starts a new physical row. Styles
+/// that need info.json fall back to the index for keys it doesn't describe.
+pub fn inlay_hints(
+    doc: &DocumentData,
+    info: Option<&KeyboardInfo>,
+    style: InlayHintStyle,
+    range: Range<usize>,
+) -> Vec<InlayHint> {
+    let mut hints = Vec::new();
+    for layer in &doc.layers {
+        if layer.span.end < range.start || layer.span.start > range.end {
+            continue;
+        }
+        let layout = info
+            .and_then(|info| info.layouts.get(&layer.macro_name))
+            .map(|l| l.layout.as_slice())
+            .unwrap_or_default();
+
+        let rows = key_rows(layout);
+        for (i, key) in layer.keys.iter().enumerate() {
+            if !range.contains(&key.start) {
+                continue;
+            }
+            let position = doc.line_index.position(&doc.content, key.start);
+            let physical = layout.get(i);
+
+            if let Some(&row) = rows.get(i)
+                && (i == 0 || rows[i - 1] != row)
+            {
+                hints.push(hint(position, format!("row {}", row), None, None));
+            }
+
+            let label = key_label(i, physical, style);
+            let tooltip = physical.map(|p| describe_key(i, p));
+            hints.push(hint(position, label, Some(InlayHintKind::PARAMETER), tooltip));
+        }
+    }
+    hints
+}
+
+/// The resolved index after every use of a layer name in `range`: enum members,
+/// keymap designators and macro arguments. A designator whose block sits at a
+/// different position in `keymaps` than its index is flagged.
+pub fn layer_index_hints(doc: &DocumentData, indices: &HashMap<String, i64>, range: Range<usize>) -> Vec<InlayHint> {
+    let mut hints = Vec::new();
+    for (i, token) in doc.tokens.iter().enumerate() {
+        if !token.is_ident() || !range.contains(&token.span.start) {
+            continue;
+        }
+        let Some(&index) = indices.get(&token.text) else {
+            continue;
+        };
+        // `_NAV = 3` and `#define _NAV 3` already spell the value out.
+        let explicit = doc.tokens.get(i + 1).is_some_and(|t| t.is_punct('='))
+            || (i > 0 && doc.tokens[i - 1].text == "define");
+        if explicit {
+            continue;
+        }
+
+        let block = doc.layers.iter().position(|l| l.name == token.text);
+        let on_designator = block.is_some_and(|b| doc.layers[b].name_span == token.span);
+        let misplaced = block.filter(|&b| b as i64 != index);
+
+        let mut label = format!("={}", index);
+        let mut tooltip = match block.map(|b| &doc.layers[b]) {
+            Some(layer) => format!("**{}** = {}\n\n`{}` · {} keys", layer.name, index, layer.macro_name, layer.key_count),
+            None => format!("**{}** = {}\n\nNo block in `keymaps`", token.text, index),
+        };
+        if let Some(block) = misplaced {
+            if on_designator {
+                label = format!("={} (block {})", index, block);
+            }
+            tooltip.push_str(&format!(
+                "\n\nBlock {} of `keymaps`: the layer enum and the keymap list layers in a different order.",
+                block
+            ));
+        }
+
+        let mut hint = hint(doc.line_index.position(&doc.content, token.span.end), label, None, None);
+        hint.padding_right = None;
+        hint.tooltip = Some(InlayHintTooltip::MarkupContent(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: tooltip,
+        }));
+        hints.push(hint);
+    }
+    hints
+}
+
+fn key_label(index: usize, key: Option<&LayoutKey>, style: InlayHintStyle) -> String {
+    match (style, key) {
+        (InlayHintStyle::Matrix, Some(LayoutKey { matrix: Some([r, c]), .. })) => format!("R{}C{}", r, c),
+        (InlayHintStyle::Label, Some(LayoutKey { label: Some(label), .. })) => label.clone(),
+        _ => format!("L{}", index),
+    }
+}
+
+fn describe_key(index: usize, key: &LayoutKey) -> String {
+    let mut parts = vec![format!("Key {}", index), format!("x {}, y {}", key.x, key.y)];
+    if let Some([r, c]) = key.matrix {
+        parts.push(format!("matrix [{}, {}]", r, c));
+    }
+    if let Some(label) = &key.label {
+        parts.push(format!("label \"{}\"", label));
+    }
+    parts.join(" · ")
+}
+
+fn hint(position: Position, label: String, kind: Option<InlayHintKind>, tooltip: Option<String>) -> InlayHint {
+    InlayHint {
+        position,
+        label: InlayHintLabel::String(label),
+        kind,
+        text_edits: None,
+        tooltip: tooltip.map(InlayHintTooltip::String),
+        padding_left: None,
+        padding_right: Some(true),
+        data: None,
+    }
+}
@@ -0,0 +1,105 @@
+use crate::document::DocumentData;
+use crate::parser::info_json::KeyboardInfo;
+use crate::parser::layers::Layer;
+use std::collections::HashMap;
+use tower_lsp::lsp_types::*;
+
+pub const LAYER_MISMATCH: &str = "layer_mismatch";
+
+/// Keycodes offered to fill the missing keys of a layer with.
+const PADDING_KEYCODES: &[&str] = &["KC_NO", "_______"];
+
+/// Where a layer's keys go past what its layout expects.
+pub fn extra_keys(layer: &Layer, expected: usize) -> &[std::ops::Range<usize>] {
+    layer.keys.get(expected..).unwrap_or_default()
+}
+
+/// Quick fixes for the `layer_mismatch` diagnostics in `diagnostics`: pad a short
+/// layer with `KC_NO` or `_______`, trim the extra keys off a long one, or switch
+/// to a layout from info.json with as many keys as the layer has.
+pub fn code_actions(uri: &Url, doc: &DocumentData, diagnostics: &[Diagnostic], info: &KeyboardInfo) -> Vec<CodeActionOrCommand> {
+    let content = &doc.content;
+    let mut actions = Vec::new();
+
+    for diagnostic in diagnostics {
+        if diagnostic.code != Some(NumberOrString::String(LAYER_MISMATCH.to_string())) {
+            continue;
+        }
+        let start = doc.line_index.offset(content, diagnostic.range.start);
+        let Some(layer) = doc.layers.iter().find(|l| l.span.start == start) else {
+            continue;
+        };
+        let Some(layout) = info.layouts.get(&layer.macro_name) else {
+            continue;
+        };
+        let expected = layout.layout.len();
+        let found = layer.key_count;
+
+        let mut siblings: Vec<&String> = info
+            .layouts
+            .iter()
+            .filter(|(name, l)| **name != layer.macro_name && l.layout.len() == found)
+            .map(|(name, _)| name)
+            .collect();
+        siblings.sort();
+
+        let mut action = |title: String, edit: TextEdit, is_preferred: bool| {
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title,
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: Some(vec![diagnostic.clone()]),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+                    ..Default::default()
+                }),
+                is_preferred: Some(is_preferred),
+                ..Default::default()
+            }));
+        };
+
+        if let Some(macro_start) = content[layer.span.clone()].find(&layer.macro_name) {
+            let macro_start = layer.span.start + macro_start;
+            for sibling in &siblings {
+                action(
+                    format!("Switch to '{}' ({} keys)", sibling, found),
+                    TextEdit {
+                        range: doc.line_index.range(content, macro_start..macro_start + layer.macro_name.len()),
+                        new_text: sibling.to_string(),
+                    },
+                    siblings.len() == 1,
+                );
+            }
+        }
+
+        if found < expected {
+            let missing = expected - found;
+            // After the last key, or right inside the parentheses of an empty layer.
+            let (at, separator) = match layer.keys.last() {
+                Some(key) => (key.end, ", "),
+                None => (layer.span.end - 1, ""),
+            };
+            for keycode in PADDING_KEYCODES {
+                action(
+                    format!("Pad '{}' with {} × {}", layer.name, missing, keycode),
+                    TextEdit {
+                        range: doc.line_index.range(content, at..at),
+                        new_text: format!("{}{}", separator, vec![*keycode; missing].join(", ")),
+                    },
+                    false,
+                );
+            }
+        } else if let (Some(last_kept), Some(last)) = (expected.checked_sub(1).and_then(|i| layer.keys.get(i)), layer.keys.last()) {
+            let extra = found - expected;
+            action(
+                format!("Remove the {} extra key{} from '{}'", extra, if extra == 1 { "" } else { "s" }, layer.name),
+                TextEdit {
+                    range: doc.line_index.range(content, last_kept.end..last.end),
+                    new_text: String::new(),
+                },
+                false,
+            );
+        }
+    }
+
+    actions
+}
@@ -0,0 +1,189 @@
+use crate::document::{DocumentData, LineIndex};
+use crate::features::definition::function_bodies;
+use crate::parser::expr::enclosing_call;
+use crate::parser::keycodes::{Keycode, KeycodeMap};
+use crate::parser::layers::{parse_layers, Layer};
+use crate::parser::macros::{MacroMap, ParamKind};
+use crate::parser::tokens::{tokenize, Token};
+use std::collections::HashMap;
+use std::ops::Range;
+use tower_lsp::lsp_types::*;
+
+pub const DEPRECATED_KEYCODE: &str = "deprecated_keycode";
+pub const MIGRATE_COMMAND: &str = "qmk.migrateKeycodes";
+
+/// A use of a renamed or removed keycode.
+pub struct DeprecatedUse<'a> {
+    pub span: Range<usize>,
+    pub keycode: &'a Keycode,
+    pub replacements: &'a [String],
+}
+
+/// Deprecated keycodes used as keycodes. Names like `DEBUG` or `RESET` are common
+/// in plain C and preprocessor code too, so only keycode positions count.
+pub fn find_deprecated<'a>(
+    content: &str,
+    tokens: &[Token],
+    layers: &[Layer],
+    macros: &MacroMap,
+    keycodes: &'a KeycodeMap,
+) -> Vec<DeprecatedUse<'a>> {
+    let handlers: Vec<Range<usize>> = function_bodies(tokens, |name| name.starts_with("process_record_")).collect();
+    tokens
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| t.is_ident())
+        .filter_map(|(i, t)| {
+            let keycode = keycodes.get(&t.text)?;
+            let deprecation = keycode.deprecated.as_ref()?;
+            if !is_keycode_position(content, tokens, i, layers, macros, &handlers) {
+                return None;
+            }
+            Some(DeprecatedUse {
+                span: t.span.clone(),
+                keycode,
+                replacements: &deprecation.replacements,
+            })
+        })
+        .collect()
+}
+
+/// Whether `tokens[i]` is used as a keycode: a key of one of `layers`, a keycode
+/// argument of a known macro, or a `case` label in one of the `handlers` bodies.
+/// Nothing on a preprocessor line counts.
+fn is_keycode_position(
+    content: &str,
+    tokens: &[Token],
+    i: usize,
+    layers: &[Layer],
+    macros: &MacroMap,
+    handlers: &[Range<usize>],
+) -> bool {
+    let token = &tokens[i];
+    if on_directive_line(content, token.span.start) || tokens.get(i + 1).is_some_and(|t| t.is_punct('(')) {
+        return false;
+    }
+    if let Some(call) = enclosing_call(tokens, token.span.start)
+        && let Some(mac) = macros.get(&call.name)
+    {
+        return mac
+            .params
+            .get(call.arg_index)
+            .is_some_and(|p| matches!(p.kind, ParamKind::Keycode | ParamKind::BasicKeycode));
+    }
+    let in_keys = layers
+        .iter()
+        .flat_map(|l| &l.keys)
+        .any(|k| k.start <= token.span.start && token.span.end <= k.end);
+    let is_case = i > 0
+        && tokens[i - 1].text == "case"
+        && tokens.get(i + 1).is_some_and(|t| t.is_punct(':'))
+        && handlers.iter().any(|body| body.contains(&i));
+    in_keys || is_case
+}
+
+/// Whether `offset` is on a `#` directive line, or a continuation of one.
+fn on_directive_line(content: &str, offset: usize) -> bool {
+    let mut start = content[..offset].rfind('\n').map_or(0, |i| i + 1);
+    // Step back over lines ending in a backslash to the directive they continue.
+    while start > 0 && content[..start - 1].ends_with('\\') {
+        start = content[..start - 1].rfind('\n').map_or(0, |i| i + 1);
+    }
+    content[start..].trim_start().starts_with('#')
+}
+
+pub fn deprecated_message(name: &str, replacements: &[String]) -> String {
+    if replacements.is_empty() {
+        return format!("'{}' has been removed from QMK.", name);
+    }
+    let names: Vec<String> = replacements.iter().map(|r| format!("'{}'", r)).collect();
+    format!("'{}' is deprecated, use {} instead.", name, names.join(" or "))
+}
+
+/// Edits replacing every deprecated keycode with its preferred replacement.
+pub fn migration_edits(
+    content: &str,
+    line_index: &LineIndex,
+    tokens: &[Token],
+    layers: &[Layer],
+    macros: &MacroMap,
+    keycodes: &KeycodeMap,
+) -> Vec<TextEdit> {
+    find_deprecated(content, tokens, layers, macros, keycodes)
+        .into_iter()
+        .filter_map(|u| {
+            Some(TextEdit {
+                range: line_index.range(content, u.span),
+                new_text: u.replacements.first()?.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Same as `migration_edits`, for a file that is not open in the editor.
+pub fn migrate_source(content: &str, keycodes: &KeycodeMap, macros: &MacroMap) -> Vec<TextEdit> {
+    let line_index = LineIndex::new(content);
+    let tokens = tokenize(content);
+    let layers = parse_layers(content);
+    migration_edits(content, &line_index, &tokens, &layers, macros, keycodes)
+}
+
+/// Quick fixes for the `deprecated_keycode` diagnostics in `diagnostics`:
+/// one action per replacement, plus one migrating the whole file.
+pub fn code_actions(uri: &Url, doc: &DocumentData, diagnostics: &[Diagnostic]) -> Vec<CodeActionOrCommand> {
+    let content = &doc.content;
+    let mut actions = Vec::new();
+    let mut fixed = Vec::new();
+
+    for diagnostic in diagnostics {
+        if diagnostic.code != Some(NumberOrString::String(DEPRECATED_KEYCODE.to_string())) {
+            continue;
+        }
+        let start = doc.line_index.offset(content, diagnostic.range.start);
+        let end = doc.line_index.offset(content, diagnostic.range.end);
+        // The client may send a stale range from before an edit.
+        let Some(name) = (start <= end).then(|| content.get(start..end)).flatten() else {
+            continue;
+        };
+        let Some(deprecation) = doc.keycodes.get(name).and_then(|kc| kc.deprecated.as_ref()) else {
+            continue;
+        };
+
+        for replacement in &deprecation.replacements {
+            let edit = TextEdit {
+                range: diagnostic.range,
+                new_text: replacement.clone(),
+            };
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title: format!("Replace '{}' with '{}'", name, replacement),
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: Some(vec![diagnostic.clone()]),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+                    ..Default::default()
+                }),
+                is_preferred: Some(deprecation.replacements.len() == 1),
+                ..Default::default()
+            }));
+        }
+        fixed.push(diagnostic.clone());
+    }
+
+    if !fixed.is_empty() {
+        let edits = migration_edits(content, &doc.line_index, &doc.tokens, &doc.layers, &doc.macros, &doc.keycodes);
+        if edits.len() > 1 {
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title: format!("Replace all {} deprecated keycodes in this file", edits.len()),
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: Some(fixed),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(HashMap::from([(uri.clone(), edits)])),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }));
+        }
+    }
+
+    actions
+}
@@ -0,0 +1,18 @@
+pub mod migrate;
+pub mod completion;
+pub mod signature_help;
+pub mod symbols;
+pub mod workspace_symbols;
+pub mod references;
+pub mod rename;
+pub mod definition;
+pub mod inlay_hints;
+pub mod format;
+pub mod semantic_tokens;
+pub mod code_lens;
+pub mod folding_ranges;
+pub mod selection_ranges;
+pub mod document_highlight;
+pub mod typos;
+pub mod layout_fixes;
+pub mod declare_keycode;
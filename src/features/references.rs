@@ -0,0 +1,186 @@
+use crate::document::LineIndex;
+use crate::parser::custom::custom_keycodes_from_enums;
+use crate::parser::defines::{parse_defines, Define};
+use crate::parser::enums::{find_layer_enum, parse_enums, parse_int, CEnum};
+use crate::parser::layers::parse_layers;
+use crate::parser::tokens::{tokenize, Token};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
+use tower_lsp::lsp_types::{Location, Url};
+
+/// A document or one of the headers it includes.
+pub struct SourceFile {
+    pub uri: Url,
+    pub content: String,
+    pub tokens: Vec<Token>,
+    pub line_index: LineIndex,
+}
+
+impl SourceFile {
+    pub fn new(uri: Url, content: String) -> Self {
+        let tokens = tokenize(&content);
+        let line_index = LineIndex::new(&content);
+        SourceFile {
+            uri,
+            content,
+            tokens,
+            line_index,
+        }
+    }
+
+    pub fn location(&self, span: Range<usize>) -> Location {
+        Location::new(self.uri.clone(), self.line_index.range(&self.content, span))
+    }
+}
+
+/// The enums and `#define`s of a file, which layer and custom keycode lookups
+/// need, parsed once so indexed files don't have to be re-read.
+#[derive(Debug, Clone, Default)]
+pub struct Declarations {
+    pub enums: Vec<CEnum>,
+    pub defines: Vec<Define>,
+}
+
+impl Declarations {
+    pub fn parse(content: &str, tokens: &[Token]) -> Self {
+        Declarations {
+            enums: parse_enums(tokens),
+            defines: parse_defines(content),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserSymbolKind {
+    Layer,
+    CustomKeycode,
+}
+
+/// A layer or custom keycode and where it is declared.
+#[derive(Debug, Clone)]
+pub struct UserSymbol {
+    pub name: String,
+    pub kind: UserSymbolKind,
+    /// Index into the searched files and the span of the enum member, if there is one.
+    pub declaration: Option<(usize, Range<usize>)>,
+}
+
+/// Finds `name` among the layers and custom keycodes of `files`.
+pub fn find_symbol(name: &str, files: &[SourceFile]) -> Option<UserSymbol> {
+    let mut kind = None;
+    let mut declaration = None;
+    for (i, file) in files.iter().enumerate() {
+        let layers = parse_layers(&file.content);
+        let layer_names: Vec<&str> = layers.iter().map(|l| l.name.as_str()).collect();
+        let enums = parse_enums(&file.tokens);
+
+        if let Some(member) = find_layer_enum(&enums, &layer_names).and_then(|e| e.member(name)) {
+            kind = Some(UserSymbolKind::Layer);
+            declaration = Some((i, member.span.clone()));
+            break;
+        }
+        if let Some(custom) = custom_keycodes_from_enums(&enums).into_iter().find(|c| c.name == name) {
+            kind = Some(UserSymbolKind::CustomKeycode);
+            declaration = Some((i, custom.span));
+            break;
+        }
+        // Layers indexed by `#define`d numbers have no enum to point at.
+        if layer_names.contains(&name) {
+            kind.get_or_insert(UserSymbolKind::Layer);
+        }
+    }
+
+    Some(UserSymbol {
+        name: name.to_string(),
+        kind: kind?,
+        declaration,
+    })
+}
+
+/// Every use of `symbol` in `files`: since comments and strings are skipped by the
+/// tokenizer, each matching identifier is a keymap index, macro argument, enum
+/// entry or `case` label.
+pub fn find_references(symbol: &UserSymbol, files: &[SourceFile], include_declaration: bool) -> Vec<Location> {
+    let mut locations = Vec::new();
+    for (i, file) in files.iter().enumerate() {
+        for token in file.tokens.iter().filter(|t| t.is_ident() && t.text == symbol.name) {
+            let is_declaration = symbol
+                .declaration
+                .as_ref()
+                .is_some_and(|(file, span)| *file == i && *span == token.span);
+            if is_declaration && !include_declaration {
+                continue;
+            }
+            locations.push(file.location(token.span.clone()));
+        }
+    }
+    locations
+}
+
+/// The numeric index of every layer name, from the layer enum or from
+/// `#define _NAV 3` style definitions, in the first file that declares it.
+pub fn layer_indices(files: &[SourceFile]) -> HashMap<String, i64> {
+    let (layer_names, declarations) = file_declarations(files);
+    let layer_names: Vec<&str> = layer_names.iter().map(String::as_str).collect();
+    declared_layer_indices(&layer_names, &declarations)
+}
+
+/// `layer_indices` for already parsed files, given the names the keymap indexes `keymaps` with.
+pub fn declared_layer_indices(layer_names: &[&str], declarations: &[Arc<Declarations>]) -> HashMap<String, i64> {
+    let mut indices = HashMap::new();
+    for file in declarations {
+        if let Some(layer_enum) = find_layer_enum(&file.enums, layer_names) {
+            for (member, value) in layer_enum.members.iter().zip(layer_enum.values()) {
+                if let Some(value) = value {
+                    indices.entry(member.name.clone()).or_insert(value);
+                }
+            }
+        }
+        for define in &file.defines {
+            if layer_names.contains(&define.name.as_str())
+                && let Some(value) = parse_int(&define.value)
+            {
+                indices.entry(define.name.clone()).or_insert(value);
+            }
+        }
+    }
+    indices
+}
+
+/// Every layer and custom keycode name declared in `files` or used as a keymap index.
+pub fn user_symbol_names(files: &[SourceFile]) -> HashMap<String, UserSymbolKind> {
+    let (layer_names, declarations) = file_declarations(files);
+    let layer_names: Vec<&str> = layer_names.iter().map(String::as_str).collect();
+    declared_symbol_names(&layer_names, &declarations)
+}
+
+/// `user_symbol_names` for already parsed files, given the names the keymap indexes `keymaps` with.
+pub fn declared_symbol_names(layer_names: &[&str], declarations: &[Arc<Declarations>]) -> HashMap<String, UserSymbolKind> {
+    let mut names: HashMap<String, UserSymbolKind> = declared_layer_indices(layer_names, declarations)
+        .into_keys()
+        .map(|name| (name, UserSymbolKind::Layer))
+        .collect();
+    for name in layer_names {
+        names.entry(name.to_string()).or_insert(UserSymbolKind::Layer);
+    }
+    for file in declarations {
+        for custom in custom_keycodes_from_enums(&file.enums) {
+            names.entry(custom.name).or_insert(UserSymbolKind::CustomKeycode);
+        }
+    }
+    names
+}
+
+/// The layer names of the first file and the declarations of every file.
+fn file_declarations(files: &[SourceFile]) -> (Vec<String>, Vec<Arc<Declarations>>) {
+    let layer_names = files
+        .first()
+        .map(|f| parse_layers(&f.content).into_iter().map(|l| l.name).collect())
+        .unwrap_or_default();
+    let declarations = files
+        .iter()
+        .map(|f| Arc::new(Declarations::parse(&f.content, &f.tokens)))
+        .collect();
+    (layer_names, declarations)
+}
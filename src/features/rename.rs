@@ -0,0 +1,67 @@
+use crate::features::references::{find_references, find_symbol, SourceFile, UserSymbol};
+use crate::parser::keycodes::KeycodeMap;
+use crate::parser::macros::{MacroMap, ModMaskMap};
+use crate::parser::tokens::token_at;
+use std::collections::HashMap;
+use tower_lsp::lsp_types::*;
+
+/// The layer or custom keycode under `offset` in the first file, with the range of its name.
+pub fn prepare_rename(files: &[SourceFile], offset: usize) -> Option<(UserSymbol, Range)> {
+    let doc = files.first()?;
+    let token = &doc.tokens[token_at(&doc.tokens, offset)?];
+    if !token.is_ident() {
+        return None;
+    }
+    let symbol = find_symbol(&token.text, files)?;
+    Some((symbol, doc.line_index.range(&doc.content, token.span.clone())))
+}
+
+/// Edits renaming every reference to `symbol`, or the reason `new_name` can't be used.
+pub fn rename_edit(
+    symbol: &UserSymbol,
+    files: &[SourceFile],
+    new_name: &str,
+    keycodes: &KeycodeMap,
+    macros: &MacroMap,
+    mod_masks: &ModMaskMap,
+) -> Result<WorkspaceEdit, String> {
+    if !is_identifier(new_name) {
+        return Err(format!("`{}` is not a valid C identifier", new_name));
+    }
+    if keycodes.contains_key(new_name) || macros.contains_key(new_name) || mod_masks.contains_key(new_name) {
+        return Err(format!("`{}` is already a QMK keycode or macro", new_name));
+    }
+    let taken = files
+        .iter()
+        .flat_map(|f| &f.tokens)
+        .any(|t| t.is_ident() && t.text == new_name);
+    if taken && new_name != symbol.name {
+        return Err(format!("`{}` is already used in this keymap", new_name));
+    }
+
+    let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+    for location in find_references(symbol, files, true) {
+        changes
+            .entry(location.uri)
+            .or_default()
+            .push(TextEdit::new(location.range, new_name.to_string()));
+    }
+    Ok(WorkspaceEdit {
+        changes: Some(changes),
+        ..Default::default()
+    })
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !C_KEYWORDS.contains(&name)
+}
+
+const C_KEYWORDS: &[&str] = &[
+    "auto", "break", "case", "char", "const", "continue", "default", "do", "double", "else", "enum",
+    "extern", "float", "for", "goto", "if", "inline", "int", "long", "register", "restrict", "return",
+    "short", "signed", "sizeof", "static", "struct", "switch", "typedef", "union", "unsigned", "void",
+    "volatile", "while", "bool", "true", "false",
+];
@@ -0,0 +1,96 @@
+use crate::document::DocumentData;
+use crate::parser::layers::{keymaps_declaration_span, keymaps_span};
+use crate::parser::tokens::TokenKind;
+use std::ops::Range;
+use tower_lsp::lsp_types::*;
+
+/// Ever larger enclosing ranges for `position`: identifier → macro call (and the
+/// calls around it) → key → `LAYOUT(...)` → layer entry → `keymaps` braces → the
+/// whole `keymaps` declaration. Enum members and table entries expand to their
+/// enum or table the same way.
+pub fn selection_range(doc: &DocumentData, position: Position) -> SelectionRange {
+    let content = &doc.content;
+    let offset = doc.line_index.offset(content, position);
+    let contains = |span: &Range<usize>| span.start <= offset && offset <= span.end;
+
+    let mut spans: Vec<Range<usize>> = Vec::new();
+    if let Some(token) = doc.tokens.iter().find(|t| t.kind != TokenKind::Punct && contains(&t.span)) {
+        spans.push(token.span.clone());
+    }
+
+    if let Some(layer) = doc.layers.iter().find(|l| contains(&l.span)) {
+        if let Some(key) = layer.keys.iter().find(|k| contains(k)) {
+            spans.extend(enclosing_calls(doc, key, offset));
+            spans.push(key.clone());
+        }
+        if let Some(macro_start) = content[layer.span.clone()].find(&layer.macro_name) {
+            spans.push(layer.span.start + macro_start..layer.span.end);
+        }
+        spans.push(layer.span.clone());
+    }
+    if let Some(keymaps) = keymaps_span(content).filter(|s| contains(s)) {
+        spans.push(keymaps);
+        spans.extend(keymaps_declaration_span(content));
+    }
+
+    for e in doc.enums.iter().filter(|e| contains(&e.span)) {
+        spans.extend(e.members.iter().map(|m| m.span.clone()).filter(|s| contains(s)));
+        spans.push(e.span.clone());
+    }
+    for table in [&doc.tap_dances, &doc.combos].into_iter().flatten().filter(|t| contains(&t.span)) {
+        spans.extend(table.entries.iter().map(|e| e.span.clone()).filter(|s| contains(s)));
+        spans.push(table.span.clone());
+    }
+
+    // Each range must strictly contain the one before it.
+    spans.sort_by_key(|s| (s.len(), std::cmp::Reverse(s.start)));
+    let mut chain: Vec<Range<usize>> = Vec::new();
+    for span in spans {
+        if chain
+            .last()
+            .is_none_or(|inner| span.start <= inner.start && inner.end <= span.end && span != *inner)
+        {
+            chain.push(span);
+        }
+    }
+
+    let mut selection: Option<SelectionRange> = None;
+    for span in chain.into_iter().rev() {
+        selection = Some(SelectionRange {
+            range: doc.line_index.range(content, span),
+            parent: selection.map(Box::new),
+        });
+    }
+    selection.unwrap_or(SelectionRange {
+        range: tower_lsp::lsp_types::Range::new(position, position),
+        parent: None,
+    })
+}
+
+/// Spans of the macro calls inside `key` that enclose `offset`, e.g. `LSFT(KC_A)`
+/// and then `LCTL(LSFT(KC_A))`.
+fn enclosing_calls(doc: &DocumentData, key: &Range<usize>, offset: usize) -> Vec<Range<usize>> {
+    let tokens: Vec<_> = doc
+        .tokens
+        .iter()
+        .filter(|t| key.start <= t.span.start && t.span.end <= key.end)
+        .collect();
+    let mut open: Vec<usize> = Vec::new();
+    let mut calls = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if token.is_punct('(') {
+            let start = match i.checked_sub(1).map(|p| tokens[p]) {
+                Some(name) if name.is_ident() => name.span.start,
+                _ => token.span.start,
+            };
+            open.push(start);
+        } else if token.is_punct(')')
+            && let Some(start) = open.pop()
+            && start <= offset
+            && offset <= token.span.end
+        {
+            calls.push(start..token.span.end);
+        }
+    }
+    calls
+}
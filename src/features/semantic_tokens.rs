@@ -0,0 +1,115 @@
+use crate::document::DocumentData;
+use crate::features::references::UserSymbolKind;
+use crate::parser::macros::ModMaskMap;
+use std::collections::HashMap;
+use std::ops::Range;
+use tower_lsp::lsp_types::*;
+
+/// Token types, in legend order. Keycode categories without a type of their own use `keycode`.
+pub const TOKEN_TYPES: &[&str] = &[
+    "keycode",
+    "modifierKeycode",
+    "mediaKeycode",
+    "quantumKeycode",
+    "lightingKeycode",
+    "customKeycode",
+    "layer",
+    "layoutMacro",
+    "functionalMacro",
+    "modMask",
+];
+
+/// Token modifiers, in legend order; `transparent` and `noop` let themes dim filler keys.
+pub const TOKEN_MODIFIERS: &[&str] = &["transparent", "noop", "deprecated"];
+
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TOKEN_TYPES.iter().map(|t| SemanticTokenType::new(t)).collect(),
+        token_modifiers: TOKEN_MODIFIERS.iter().map(|m| SemanticTokenModifier::new(m)).collect(),
+    }
+}
+
+fn token_type(name: &str) -> u32 {
+    TOKEN_TYPES.iter().position(|t| *t == name).unwrap() as u32
+}
+
+fn modifier(name: &str) -> u32 {
+    1 << TOKEN_MODIFIERS.iter().position(|m| *m == name).unwrap()
+}
+
+/// Delta-encoded tokens for the identifiers in `range` (or the whole document)
+/// that name keycodes, layers, layout or functional macros and mod masks.
+/// `names` holds the document's layers and custom keycodes, including those
+/// declared in included headers.
+pub fn semantic_tokens(
+    doc: &DocumentData,
+    names: &HashMap<String, UserSymbolKind>,
+    mod_masks: &ModMaskMap,
+    range: Option<Range<usize>>,
+) -> Vec<SemanticToken> {
+    let mut tokens = Vec::new();
+    let mut previous = Position::new(0, 0);
+
+    for (i, token) in doc.tokens.iter().enumerate() {
+        if !token.is_ident() || range.as_ref().is_some_and(|r| !r.contains(&token.span.start)) {
+            continue;
+        }
+        let name = token.text.as_str();
+        let is_call = doc.tokens.get(i + 1).is_some_and(|t| t.is_punct('('));
+
+        let classified = if let Some(keycode) = doc.keycodes.get(name) {
+            let kind = match keycode.category.as_str() {
+                "modifier" => "modifierKeycode",
+                "media" => "mediaKeycode",
+                "quantum" => "quantumKeycode",
+                "lighting" => "lightingKeycode",
+                _ => "keycode",
+            };
+            let mut modifiers = 0;
+            if keycode.is_transparent() {
+                modifiers |= modifier("transparent");
+            }
+            if keycode.is_noop() {
+                modifiers |= modifier("noop");
+            }
+            if keycode.deprecated.is_some() {
+                modifiers |= modifier("deprecated");
+            }
+            Some((kind, modifiers))
+        } else if let Some(kind) = names.get(name) {
+            match kind {
+                UserSymbolKind::Layer => Some(("layer", 0)),
+                UserSymbolKind::CustomKeycode => Some(("customKeycode", 0)),
+            }
+        } else if is_call && doc.macros.contains_key(name) {
+            Some(("functionalMacro", 0))
+        } else if is_call && (name.starts_with("LAYOUT") || doc.layers.iter().any(|l| l.macro_name == name)) {
+            Some(("layoutMacro", 0))
+        } else if mod_masks.contains_key(name) {
+            Some(("modMask", 0))
+        } else {
+            None
+        };
+        let Some((kind, modifiers)) = classified else {
+            continue;
+        };
+
+        let start = doc.line_index.position(&doc.content, token.span.start);
+        let end = doc.line_index.position(&doc.content, token.span.end);
+        let delta_line = start.line - previous.line;
+        let delta_start = if delta_line == 0 {
+            start.character - previous.character
+        } else {
+            start.character
+        };
+        tokens.push(SemanticToken {
+            delta_line,
+            delta_start,
+            length: end.character - start.character,
+            token_type: token_type(kind),
+            token_modifiers_bitset: modifiers,
+        });
+        previous = start;
+    }
+    tokens
+}
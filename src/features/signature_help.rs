@@ -0,0 +1,44 @@
+use crate::document::DocumentData;
+use crate::parser::expr::enclosing_call;
+use tower_lsp::lsp_types::*;
+
+/// Signature of the macro call around `offset`, with the argument under the cursor active.
+pub fn signature_help(doc: &DocumentData, offset: usize) -> Option<SignatureHelp> {
+    let call = enclosing_call(&doc.tokens, offset)?;
+    let mac = doc.macros.get(&call.name)?;
+    // Past the last parameter there is nothing to highlight, and clients read a
+    // missing or out-of-range active parameter as the first one.
+    if call.arg_index >= mac.params.len() {
+        return None;
+    }
+
+    let label = mac.signature();
+    let mut parameters = Vec::new();
+    let mut start = mac.name.encode_utf16().count() as u32 + 1;
+    for param in &mac.params {
+        let end = start + param.name.encode_utf16().count() as u32;
+        parameters.push(ParameterInformation {
+            label: ParameterLabel::LabelOffsets([start, end]),
+            documentation: Some(Documentation::String(format!("{}: {}", param.name, param.kind.describe()))),
+        });
+        // Skip the ", " separator.
+        start = end + 2;
+    }
+
+    let active_parameter = Some(call.arg_index as u32);
+    let signature = SignatureInformation {
+        label,
+        documentation: Some(Documentation::MarkupContent(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: format!("**{}**\n\n{}\n\n{}", mac.detail, mac.description, mac.doc),
+        })),
+        parameters: Some(parameters),
+        active_parameter,
+    };
+
+    Some(SignatureHelp {
+        signatures: vec![signature],
+        active_signature: Some(0),
+        active_parameter,
+    })
+}
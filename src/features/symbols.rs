@@ -0,0 +1,136 @@
+use crate::document::DocumentData;
+use crate::parser::custom::is_custom_keycode_enum;
+use crate::parser::enums::find_layer_enum;
+use crate::parser::tables::{parse_table, Table};
+use std::ops::Range;
+use tower_lsp::lsp_types::*;
+
+/// Outline of a keymap: the `keymaps` array and its layers, enums and feature tables,
+/// in source order.
+pub fn document_symbols(doc: &DocumentData) -> Vec<DocumentSymbol> {
+    let mut symbols = Vec::new();
+
+    if let Some(keymaps) = parse_table(&doc.tokens, "keymaps") {
+        let children = doc
+            .layers
+            .iter()
+            .map(|layer| {
+                symbol(
+                    doc,
+                    layer.name.clone(),
+                    Some(format!("{} · {} keys", layer.macro_name, layer.key_count)),
+                    SymbolKind::OBJECT,
+                    layer.span.clone(),
+                    layer.name_span.clone(),
+                    None,
+                )
+            })
+            .collect();
+        let name_span = keymaps.span.start..keymaps.span.start + keymaps.name.len();
+        symbols.push(symbol(
+            doc,
+            keymaps.name,
+            Some(format!("{} layers", doc.layers.len())),
+            SymbolKind::ARRAY,
+            keymaps.span,
+            name_span,
+            Some(children),
+        ));
+    }
+
+    let layer_names: Vec<&str> = doc.layers.iter().map(|l| l.name.as_str()).collect();
+    let layer_enum = find_layer_enum(&doc.enums, &layer_names);
+    for e in &doc.enums {
+        let detail = if layer_enum.is_some_and(|l| std::ptr::eq(l, e)) {
+            Some("Layers".to_string())
+        } else if is_custom_keycode_enum(e) {
+            Some("Custom Keycodes".to_string())
+        } else {
+            None
+        };
+        let children = e
+            .members
+            .iter()
+            .map(|m| {
+                symbol(
+                    doc,
+                    m.name.clone(),
+                    m.value.clone(),
+                    SymbolKind::ENUM_MEMBER,
+                    m.span.clone(),
+                    m.span.clone(),
+                    None,
+                )
+            })
+            .collect();
+        let name = e.name.clone().unwrap_or_else(|| "enum".to_string());
+        symbols.push(symbol(
+            doc,
+            name,
+            detail,
+            SymbolKind::ENUM,
+            e.span.clone(),
+            e.name_span.clone(),
+            Some(children),
+        ));
+    }
+
+    for (table, detail) in [(&doc.tap_dances, "Tap Dance"), (&doc.combos, "Combo")] {
+        if let Some(table) = table {
+            symbols.push(table_symbol(doc, table, detail));
+        }
+    }
+
+    symbols.sort_by_key(|s| (s.range.start.line, s.range.start.character));
+    symbols
+}
+
+fn table_symbol(doc: &DocumentData, table: &Table, detail: &str) -> DocumentSymbol {
+    let children = table
+        .entries
+        .iter()
+        .map(|entry| {
+            symbol(
+                doc,
+                entry.name.clone(),
+                Some(detail.to_string()),
+                SymbolKind::STRUCT,
+                entry.span.clone(),
+                entry.name_span.clone(),
+                None,
+            )
+        })
+        .collect();
+    let name_span = table.span.start..table.span.start + table.name.len();
+    symbol(
+        doc,
+        table.name.clone(),
+        Some(format!("{} entries", table.entries.len())),
+        SymbolKind::ARRAY,
+        table.span.clone(),
+        name_span,
+        Some(children),
+    )
+}
+
+#[allow(deprecated)]
+fn symbol(
+    doc: &DocumentData,
+    name: String,
+    detail: Option<String>,
+    kind: SymbolKind,
+    span: Range<usize>,
+    name_span: Range<usize>,
+    children: Option<Vec<DocumentSymbol>>,
+) -> DocumentSymbol {
+    DocumentSymbol {
+        name,
+        detail,
+        kind,
+        tags: None,
+        deprecated: None,
+        range: doc.line_index.range(&doc.content, span),
+        selection_range: doc.line_index.range(&doc.content, name_span),
+        children,
+    }
+}
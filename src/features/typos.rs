@@ -0,0 +1,172 @@
+use crate::document::DocumentData;
+use crate::parser::keycodes::KeycodeMap;
+use crate::parser::layers::Layer;
+use crate::parser::tokens::Token;
+use std::collections::HashMap;
+use tower_lsp::lsp_types::*;
+
+pub const UNKNOWN_KEYCODE: &str = "unknown_keycode";
+
+/// Most suggestions offered for one unknown keycode.
+const MAX_SUGGESTIONS: usize = 5;
+
+/// Whether `name` is written like a basic keycode, in any case (`KC_A`, `kc_a`).
+pub fn looks_like_keycode(name: &str) -> bool {
+    name.get(..3).is_some_and(|prefix| prefix.eq_ignore_ascii_case("KC_"))
+}
+
+/// The prefix most of the locale keycodes in `names` share, such as `DE_` for the
+/// symbols of `keymap_german.h`.
+pub fn locale_prefix(names: &[String]) -> Option<&str> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for name in names {
+        if let Some(end) = name.find('_').filter(|&end| end > 0) {
+            *counts.entry(&name[..=end]).or_insert(0) += 1;
+        }
+    }
+    counts
+        .into_iter()
+        .filter(|(prefix, _)| *prefix != "KC_")
+        .max_by_key(|&(prefix, count)| (count, std::cmp::Reverse(prefix)))
+        .map(|(prefix, _)| prefix)
+}
+
+/// Identifiers that should be keycodes but aren't `known`: `KC_` names anywhere,
+/// and, written as keys of `layers`, `kc_` names in any case and names with the
+/// prefix of the included `locale` keycodes. Other lower-case names are C code.
+pub fn unknown_keycodes<'a>(tokens: &'a [Token], layers: &[Layer], locale: &[String], known: impl Fn(&str) -> bool) -> Vec<&'a Token> {
+    let prefix = locale_prefix(locale);
+    let in_keys = |token: &Token| {
+        layers
+            .iter()
+            .flat_map(|l| &l.keys)
+            .any(|k| k.start <= token.span.start && token.span.end <= k.end)
+    };
+    tokens
+        .iter()
+        .filter(|t| t.is_ident() && !known(&t.text))
+        .filter(|t| {
+            let text = t.text.as_str();
+            if text.starts_with("KC_") {
+                return true;
+            }
+            let is_locale = prefix.is_some_and(|p| text.starts_with(p)) && !locale.iter().any(|l| l == text);
+            (looks_like_keycode(text) || is_locale) && in_keys(t)
+        })
+        .collect()
+}
+
+/// Known names closest to `name`, best first. Case is ignored when comparing,
+/// so `kc_a` suggests `KC_A`, and every keycode suggested brings its aliases
+/// along (`KC_ENT` and `KC_ENTER`). `extra` holds custom and locale keycodes.
+pub fn suggestions(name: &str, keycodes: &KeycodeMap, extra: &[String]) -> Vec<String> {
+    let upper = name.to_ascii_uppercase();
+    let max_distance = (name.len() / 4).clamp(1, 3);
+
+    let candidates = keycodes
+        .values()
+        .filter(|kc| kc.deprecated.is_none())
+        .map(|kc| kc.name.as_str())
+        .chain(extra.iter().map(String::as_str));
+    let mut scored: Vec<(usize, usize, &str)> = candidates
+        .filter(|c| *c != name)
+        .filter_map(|c| {
+            let distance = edit_distance(&upper, &c.to_ascii_uppercase());
+            (distance <= max_distance).then(|| (distance, c.len().abs_diff(name.len()), c))
+        })
+        .collect();
+    scored.sort();
+    scored.dedup_by_key(|(_, _, c)| *c);
+
+    let mut found: Vec<String> = Vec::new();
+    for (_, _, candidate) in scored {
+        for name in std::iter::once(candidate).chain(aliases(candidate, keycodes)) {
+            if !found.iter().any(|f| f == name) {
+                found.push(name.to_string());
+            }
+        }
+        if found.len() >= MAX_SUGGESTIONS {
+            break;
+        }
+    }
+    found.truncate(MAX_SUGGESTIONS);
+    found
+}
+
+/// The other names of the keycode called `name`, canonical name first.
+fn aliases<'a>(name: &str, keycodes: &'a KeycodeMap) -> Vec<&'a str> {
+    let Some(keycode) = keycodes.get(name) else {
+        return Vec::new();
+    };
+    let canonical = keycode.alias_of().unwrap_or(&keycode.name);
+    let mut names: Vec<&str> = keycodes
+        .values()
+        .filter(|kc| kc.deprecated.is_none() && kc.name != name)
+        .filter(|kc| kc.name == canonical || kc.alias_of() == Some(canonical))
+        .map(|kc| kc.name.as_str())
+        .collect();
+    names.sort_by_key(|n| (*n != canonical, *n));
+    names
+}
+
+/// Optimal string alignment distance: insertions, deletions, substitutions and
+/// transpositions of adjacent characters each cost one.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    rows[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (rows[i - 1][j] + 1).min(rows[i][j - 1] + 1).min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = distance;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+/// Quick fixes for the `unknown_keycode` diagnostics in `diagnostics`, one per
+/// suggested name, the closest one preferred.
+pub fn code_actions(uri: &Url, doc: &DocumentData, diagnostics: &[Diagnostic], extra: &[String]) -> Vec<CodeActionOrCommand> {
+    let content = &doc.content;
+    let mut actions = Vec::new();
+
+    for diagnostic in diagnostics {
+        if diagnostic.code != Some(NumberOrString::String(UNKNOWN_KEYCODE.to_string())) {
+            continue;
+        }
+        let start = doc.line_index.offset(content, diagnostic.range.start);
+        let end = doc.line_index.offset(content, diagnostic.range.end);
+        // The client may send a stale range from before an edit.
+        let Some(name) = (start <= end).then(|| content.get(start..end)).flatten() else {
+            continue;
+        };
+
+        for (i, suggestion) in suggestions(name, &doc.keycodes, extra).into_iter().enumerate() {
+            let edit = TextEdit {
+                range: diagnostic.range,
+                new_text: suggestion.clone(),
+            };
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title: format!("Replace '{}' with '{}'", name, suggestion),
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: Some(vec![diagnostic.clone()]),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+                    ..Default::default()
+                }),
+                is_preferred: Some(i == 0),
+                ..Default::default()
+            }));
+        }
+    }
+
+    actions
+}
@@ -0,0 +1,88 @@
+use crate::workspace::{IndexedKind, IndexedSymbol, WorkspaceIndex};
+use std::path::PathBuf;
+use tower_lsp::lsp_types::*;
+
+/// Upper bound on results, so an empty query doesn't ship the whole index.
+pub const MAX_SYMBOLS: usize = 256;
+
+/// Scores `name` against `query` as a case-insensitive subsequence match,
+/// favouring consecutive characters and matches at word starts (`_GAMING`
+/// for "gam", `KC_VIM_SAVE` for "vs"). None if `query` doesn't match.
+pub fn fuzzy_score(query: &str, name: &str) -> Option<i64> {
+    let name: Vec<char> = name.chars().collect();
+    let mut score = 0;
+    let mut next = 0;
+    let mut previous: Option<usize> = None;
+
+    for q in query.chars().filter(|c| !c.is_whitespace()) {
+        let found = (next..name.len()).find(|&i| name[i].eq_ignore_ascii_case(&q))?;
+        score += 1;
+        if previous.is_some_and(|p| p + 1 == found) {
+            score += 4;
+        }
+        if found == 0 || name[found - 1] == '_' || (name[found].is_uppercase() && name[found - 1].is_lowercase()) {
+            score += 3;
+        }
+        // Gaps cost a little so tighter matches win ties.
+        score -= (found - next) as i64 / 4;
+        previous = Some(found);
+        next = found + 1;
+    }
+
+    // Shorter names are closer to what was typed.
+    Some(score * 100 - name.len() as i64)
+}
+
+/// Searches the index, with `open` documents taking precedence over their indexed copies.
+pub fn workspace_symbols(
+    index: &WorkspaceIndex,
+    open: &[(Url, Vec<IndexedSymbol>)],
+    query: &str,
+) -> Vec<SymbolInformation> {
+    let open_paths: Vec<PathBuf> = open.iter().filter_map(|(uri, _)| uri.to_file_path().ok()).collect();
+    let indexed = index
+        .files
+        .iter()
+        .filter(|(path, _)| !open_paths.contains(path))
+        .filter_map(|(path, symbols)| Some((Url::from_file_path(path).ok()?, symbols)));
+    let files = indexed.chain(open.iter().map(|(uri, symbols)| (uri.clone(), symbols)));
+
+    let mut matches = Vec::new();
+    for (uri, symbols) in files {
+        for symbol in symbols {
+            if let Some(score) = fuzzy_score(query, &symbol.name) {
+                matches.push((score, uri.clone(), symbol));
+            }
+        }
+    }
+    matches.sort_by(|a, b| {
+        b.0.cmp(&a.0)
+            .then_with(|| a.2.name.cmp(&b.2.name))
+            .then_with(|| a.2.container.cmp(&b.2.container))
+    });
+    matches.truncate(MAX_SYMBOLS);
+
+    matches
+        .into_iter()
+        .map(|(_, uri, symbol)| symbol_information(uri, symbol))
+        .collect()
+}
+
+#[allow(deprecated)]
+fn symbol_information(uri: Url, symbol: &IndexedSymbol) -> SymbolInformation {
+    let kind = match symbol.kind {
+        IndexedKind::Layer => SymbolKind::OBJECT,
+        IndexedKind::CustomKeycode => SymbolKind::ENUM_MEMBER,
+        IndexedKind::LayoutMacro => SymbolKind::FUNCTION,
+        IndexedKind::Layout => SymbolKind::STRUCT,
+    };
+    SymbolInformation {
+        name: symbol.name.clone(),
+        kind,
+        tags: None,
+        deprecated: None,
+        location: Location::new(uri, symbol.range),
+        container_name: Some(symbol.container.clone()),
+    }
+}
+
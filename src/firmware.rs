@@ -0,0 +1,129 @@
+use crate::document::LineIndex;
+use crate::parser::defines::parse_defines;
+use crate::parser::enums::parse_enums;
+use crate::parser::tokens::{tokenize, Token};
+use crate::workspace::walk;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::sync::OnceCell;
+use tower_lsp::lsp_types::{Location, Range, Url};
+
+/// Firmware directories holding the public headers keymaps use.
+const INDEXED_DIRS: &[&str] = &["quantum", "tmk_core", "drivers", "platforms"];
+
+/// Marker distinguishing a `qmk_firmware` checkout from any other directory.
+const FIRMWARE_MARKER: &str = "quantum/keycodes.h";
+
+pub fn is_firmware_root(path: &Path) -> bool {
+    path.join(FIRMWARE_MARKER).is_file()
+}
+
+/// Where the firmware declares each symbol: `#define`s, enum members and function prototypes.
+#[derive(Debug, Default)]
+pub struct FirmwareIndex {
+    pub symbols: HashMap<String, (PathBuf, Range)>,
+}
+
+impl FirmwareIndex {
+    pub fn build(root: &Path) -> Self {
+        let mut headers = Vec::new();
+        for dir in INDEXED_DIRS {
+            walk(&root.join(dir), &mut |path| {
+                if path.extension().is_some_and(|e| e == "h") {
+                    headers.push(path.to_path_buf());
+                }
+            });
+        }
+        // Generated keycode headers first so their declarations win over later re-definitions.
+        headers.sort_by_key(|p| (!p.ends_with(FIRMWARE_MARKER), p.clone()));
+
+        let mut index = FirmwareIndex::default();
+        for path in headers {
+            if let Ok(content) = fs::read_to_string(&path) {
+                index.add_header(&path, &content);
+            }
+        }
+        index
+    }
+
+    fn add_header(&mut self, path: &Path, content: &str) {
+        let line_index = LineIndex::new(content);
+        let tokens = tokenize(content);
+
+        let defines = parse_defines(content).into_iter().map(|d| (d.name, d.name_span));
+        let members = parse_enums(&tokens)
+            .into_iter()
+            .flat_map(|e| e.members)
+            .map(|m| (m.name, m.span));
+        let functions = function_declarations(&tokens).map(|t| (t.text.clone(), t.span.clone()));
+
+        for (name, span) in defines.chain(members).chain(functions) {
+            self.symbols
+                .entry(name)
+                .or_insert_with(|| (path.to_path_buf(), line_index.range(content, span)));
+        }
+    }
+
+    /// Symbols declared in firmware headers named `header`, such as the locale
+    /// keycodes of `keymap_german.h`.
+    pub fn header_symbols<'a>(&'a self, header: &'a str) -> impl Iterator<Item = &'a str> {
+        let file_name = Path::new(header).file_name();
+        self.symbols
+            .iter()
+            .filter(move |(_, (path, _))| path.file_name() == file_name)
+            .map(|(name, _)| name.as_str())
+    }
+
+    pub fn location(&self, name: &str) -> Option<Location> {
+        let (path, range) = self.symbols.get(name)?;
+        Some(Location::new(Url::from_file_path(path).ok()?, *range))
+    }
+}
+
+/// Names of top-level function prototypes such as `void layer_on(uint8_t layer);`.
+fn function_declarations(tokens: &[Token]) -> impl Iterator<Item = &Token> {
+    let mut depth = 0;
+    tokens.iter().enumerate().filter_map(move |(i, token)| {
+        if token.is_punct('{') {
+            depth += 1;
+        } else if token.is_punct('}') {
+            depth -= 1;
+        }
+        let is_call = depth == 0
+            && token.is_ident()
+            && tokens.get(i + 1).is_some_and(|t| t.is_punct('('));
+        // A return type or pointer before the name; `#define`s and keywords don't count.
+        let after_type = i > 0
+            && (tokens[i - 1].is_punct('*')
+                || (tokens[i - 1].is_ident() && !["define", "return", "if", "defined", "sizeof"].contains(&tokens[i - 1].text.as_str())));
+        (is_call && after_type).then_some(token)
+    })
+}
+
+/// A firmware checkout whose index is built on first use.
+pub struct FirmwareSource {
+    pub root: PathBuf,
+    index: OnceCell<FirmwareIndex>,
+}
+
+impl FirmwareSource {
+    pub fn new(root: PathBuf) -> Self {
+        FirmwareSource {
+            root,
+            index: OnceCell::new(),
+        }
+    }
+
+    /// The index, waiting for a build in progress.
+    pub async fn index(&self) -> &FirmwareIndex {
+        self.index
+            .get_or_init(|| async {
+                let root = self.root.clone();
+                tokio::task::spawn_blocking(move || FirmwareIndex::build(&root))
+                    .await
+                    .unwrap_or_default()
+            })
+            .await
+    }
+}
@@ -0,0 +1,78 @@
+use crate::parser::expr::{parse_key_expr, KeyExpr};
+use crate::parser::keycodes::KeycodeMap;
+use crate::parser::macros::{MacroMap, ModMaskMap};
+
+/// The databases a legend is rendered against.
+pub struct LegendContext<'a> {
+    pub keycodes: &'a KeycodeMap,
+    pub macros: &'a MacroMap,
+    pub mod_masks: &'a ModMaskMap,
+}
+
+/// Renders the key written as `text` in a keymap, e.g. `LT(_NAV, KC_SPC)` → "Spc / Nav".
+pub fn key_legend(text: &str, ctx: &LegendContext) -> String {
+    render_legend(&parse_key_expr(text), ctx)
+}
+
+pub fn render_legend(expr: &KeyExpr, ctx: &LegendContext) -> String {
+    match expr {
+        KeyExpr::Ident(name) => ident_legend(name, ctx),
+        KeyExpr::Number(n) => n.clone(),
+        KeyExpr::Other(text) => text.clone(),
+        KeyExpr::Or(operands) => {
+            let legends: Vec<String> = operands.iter().map(|o| render_legend(o, ctx)).collect();
+            let all_mods = operands
+                .iter()
+                .all(|o| matches!(o, KeyExpr::Ident(name) if ctx.mod_masks.contains_key(name)));
+            legends.join(if all_mods { "" } else { " | " })
+        }
+        KeyExpr::Call { name, args } => {
+            let rendered: Vec<String> = args.iter().map(|a| render_legend(a, ctx)).collect();
+            let Some(mac) = ctx.macros.get(name) else {
+                return format!("{}({})", name, rendered.join(", "));
+            };
+            let Some(template) = &mac.legend else {
+                return format!("{}({})", name, rendered.join(", "));
+            };
+
+            let mut legend = template.clone();
+            for (i, param) in mac.params.iter().enumerate() {
+                let value = rendered.get(i).map_or("?", |s| s.as_str());
+                legend = legend.replace(&format!("{{{}}}", param.name), value);
+            }
+            legend
+        }
+    }
+}
+
+fn ident_legend(name: &str, ctx: &LegendContext) -> String {
+    if let Some(legend) = ctx.keycodes.get(name).and_then(|kc| kc.legend.as_ref()) {
+        return legend.clone();
+    }
+    if let Some(legend) = ctx.mod_masks.get(name).and_then(|m| m.legend.as_ref()) {
+        return legend.clone();
+    }
+    humanize(name)
+}
+
+/// Turns an identifier into a readable label: `_NAV` → "Nav", `KC_VIM_SAVE` → "Vim Save".
+/// Used for layers, custom keycodes and keycodes without a legend.
+pub fn humanize(name: &str) -> String {
+    let stripped = name.strip_prefix("KC_").unwrap_or(name);
+    let words: Vec<String> = stripped
+        .split('_')
+        .filter(|w| !w.is_empty())
+        .map(|w| {
+            let mut chars = w.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars.flat_map(|c| c.to_lowercase())).collect(),
+                None => String::new(),
+            }
+        })
+        .collect();
+    if words.is_empty() {
+        name.to_string()
+    } else {
+        words.join(" ")
+    }
+}
@@ -1,3 +1,8 @@
+pub mod config;
 pub mod document;
+pub mod features;
+pub mod firmware;
+pub mod legend;
 pub mod parser;
 pub mod server;
+pub mod workspace;
@@ -7,7 +7,7 @@ async fn main() {
     let stdin = tokio::io::stdin();
     let stdout = tokio::io::stdout();
 
-    let (service, socket) = LspService::new(|client| Backend::new(client));
+    let (service, socket) = LspService::new(Backend::new);
     eprintln!("Service created, listening on stdin/stdout");
     Server::new(stdin, stdout, socket).serve(service).await;
     eprintln!("Server shutdown.");
@@ -1,42 +1,40 @@
-use regex::Regex;
+use crate::parser::enums::{parse_enums, CEnum};
+use crate::parser::tokens::tokenize;
+use std::ops::Range;
 
 #[derive(Debug, Clone)]
 pub struct CustomKeycode {
     pub name: String,
+    pub span: Range<usize>,
 }
 
-pub fn parse_custom_keycodes(content: &str) -> Vec<CustomKeycode> {
-    let re = Regex::new(r"enum\s+custom_keycodes\s*\{(?P<body>[^}]+)\};").unwrap();
-    let mut keycodes = Vec::new();
+/// Values custom keycode ranges conventionally start from.
+const CUSTOM_RANGE_STARTS: &[&str] = &["SAFE_RANGE", "QK_USER", "NEW_SAFE_RANGE", "USER_SAFE_RANGE"];
 
-    if let Some(caps) = re.captures(content) {
-        let body = &caps["body"];
-        
-        // Remove comments line by line
-        let clean_lines: Vec<&str> = body.lines().map(|line| {
-            if let Some(idx) = line.find("//") {
-                &line[..idx]
-            } else {
-                line
-            }
-        }).collect();
-        let clean_body = clean_lines.join(" ");
+/// Whether `e` declares custom keycodes: `enum custom_keycodes`, or any enum
+/// starting a member at `SAFE_RANGE` (as userspace keycode enums do).
+pub fn is_custom_keycode_enum(e: &CEnum) -> bool {
+    e.name.as_deref() == Some("custom_keycodes")
+        || e.members.iter().any(|m| {
+            m.value
+                .as_deref()
+                .map(|v| v.split(|c: char| !c.is_ascii_alphanumeric() && c != '_').next().unwrap_or(v))
+                .is_some_and(|v| CUSTOM_RANGE_STARTS.contains(&v))
+        })
+}
 
-        // Split by comma and clean up
-        for part in clean_body.split(',') {
-            let trimmed = part.trim();
-            if !trimmed.is_empty() {
-                // Handle potential assignment like MY_KEY = SAFE_RANGE
-                let name = trimmed.split('=').next().unwrap_or(trimmed).trim();
-                // Ensure it looks like a keycode (alphanumeric + count > 0)
-                 if !name.is_empty() {
-                     keycodes.push(CustomKeycode {
-                        name: name.to_string(),
-                    });
-                 }
-            }
-        }
-    }
-    keycodes
+pub fn custom_keycodes_from_enums(enums: &[CEnum]) -> Vec<CustomKeycode> {
+    enums
+        .iter()
+        .filter(|e| is_custom_keycode_enum(e))
+        .flat_map(|e| &e.members)
+        .map(|m| CustomKeycode {
+            name: m.name.clone(),
+            span: m.span.clone(),
+        })
+        .collect()
 }
 
+pub fn parse_custom_keycodes(content: &str) -> Vec<CustomKeycode> {
+    custom_keycodes_from_enums(&parse_enums(&tokenize(content)))
+}
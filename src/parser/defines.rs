@@ -0,0 +1,125 @@
+use regex::Regex;
+use std::ops::Range;
+
+/// A `#define NAME value` or function-like `#define NAME(a, b) value`.
+#[derive(Debug, Clone)]
+pub struct Define {
+    pub name: String,
+    pub name_span: Range<usize>,
+    /// Parameter names of a function-like macro.
+    pub params: Option<Vec<String>>,
+    /// The replacement text, with line continuations joined.
+    pub value: String,
+    /// The whole directive, including continuation lines.
+    pub span: Range<usize>,
+}
+
+pub fn parse_defines(content: &str) -> Vec<Define> {
+    let re = Regex::new(r"(?m)^[ \t]*#[ \t]*define[ \t]+([A-Za-z_][A-Za-z0-9_]*)").unwrap();
+    let mut defines = Vec::new();
+
+    for cap in re.captures_iter(content) {
+        let whole = cap.get(0).unwrap();
+        let name = cap.get(1).unwrap();
+
+        // The directive runs to the first newline not escaped by a backslash.
+        let mut end = name.end();
+        loop {
+            match content[end..].find('\n') {
+                Some(i) if content[..end + i].trim_end_matches('\r').ends_with('\\') => end += i + 1,
+                Some(i) => {
+                    end += i;
+                    break;
+                }
+                None => {
+                    end = content.len();
+                    break;
+                }
+            }
+        }
+        let mut rest = &content[name.end()..end];
+
+        // Only a parenthesis directly after the name makes a function-like macro.
+        let mut params = None;
+        if rest.starts_with('(')
+            && let Some(close) = rest.find(')')
+        {
+            params = Some(
+                rest[1..close]
+                    .split(',')
+                    .map(|p| p.trim().to_string())
+                    .filter(|p| !p.is_empty())
+                    .collect(),
+            );
+            rest = &rest[close + 1..];
+        }
+
+        let value = rest
+            .lines()
+            .map(|l| strip_line_comment(l).trim().trim_end_matches('\\').trim())
+            .filter(|l| !l.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        defines.push(Define {
+            name: name.as_str().to_string(),
+            name_span: name.range(),
+            params,
+            value,
+            span: whole.start()..end,
+        });
+    }
+
+    defines
+}
+
+/// Drops a trailing `// comment`, leaving `//` inside URLs (`"https://..."`) alone.
+fn strip_line_comment(line: &str) -> &str {
+    let mut search = 0;
+    while let Some(i) = line[search..].find("//") {
+        let at = search + i;
+        if at == 0 || line[..at].ends_with(char::is_whitespace) {
+            return &line[..at];
+        }
+        search = at + 2;
+    }
+    line
+}
+
+/// Byte ranges of the branches of every `#if`/`#ifdef`/`#ifndef` block. Each
+/// branch runs from the start of its directive line to the start of the next
+/// `#elif`, `#else` or `#endif` line. Unterminated blocks are dropped.
+pub fn parse_conditional_branches(content: &str) -> Vec<Range<usize>> {
+    let mut branches = Vec::new();
+    let mut open: Vec<usize> = Vec::new();
+    let mut line_start = 0;
+
+    for line in content.split_inclusive('\n') {
+        if let Some(directive) = line.trim_start().strip_prefix('#') {
+            let word: String = directive
+                .trim_start()
+                .chars()
+                .take_while(|c| c.is_ascii_alphabetic())
+                .collect();
+            match word.as_str() {
+                "if" | "ifdef" | "ifndef" => open.push(line_start),
+                "elif" | "elifdef" | "elifndef" | "else" => {
+                    if let Some(start) = open.pop() {
+                        branches.push(start..line_start);
+                        open.push(line_start);
+                    }
+                }
+                "endif" => {
+                    if let Some(start) = open.pop() {
+                        branches.push(start..line_start);
+                    }
+                }
+                _ => {}
+            }
+        }
+        line_start += line.len();
+    }
+
+    branches.sort_by_key(|b| b.start);
+    branches
+}
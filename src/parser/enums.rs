@@ -0,0 +1,157 @@
+use crate::parser::tokens::Token;
+use std::ops::Range;
+
+/// A C `enum` declaration.
+#[derive(Debug, Clone)]
+pub struct CEnum {
+    pub name: Option<String>,
+    /// From the `enum` keyword to the closing brace.
+    pub span: Range<usize>,
+    /// The name, or the `enum` keyword of an anonymous enum.
+    pub name_span: Range<usize>,
+    pub members: Vec<EnumMember>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EnumMember {
+    pub name: String,
+    pub span: Range<usize>,
+    /// The initializer, if any, with whitespace and comments removed.
+    pub value: Option<String>,
+}
+
+impl CEnum {
+    pub fn member(&self, name: &str) -> Option<&EnumMember> {
+        self.members.iter().find(|m| m.name == name)
+    }
+
+    /// The value of each member, following C's counting rules. Initializers
+    /// other than integer literals and earlier members (`_RAISE = _LOWER`)
+    /// can't be resolved, and neither can anything counted from them.
+    pub fn values(&self) -> Vec<Option<i64>> {
+        let mut values: Vec<Option<i64>> = Vec::new();
+        let mut next = Some(0);
+        for member in &self.members {
+            let value = match &member.value {
+                None => next,
+                Some(value) => parse_int(value).or_else(|| {
+                    let i = self.members.iter().position(|m| m.name == *value)?;
+                    *values.get(i)?
+                }),
+            };
+            values.push(value);
+            next = value.map(|v| v + 1);
+        }
+        values
+    }
+}
+
+/// Parses a decimal, hex or binary integer literal, ignoring `U`/`L` suffixes.
+pub fn parse_int(text: &str) -> Option<i64> {
+    let text = text.trim_end_matches(['u', 'U', 'l', 'L']);
+    if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()
+    } else if let Some(bin) = text.strip_prefix("0b").or_else(|| text.strip_prefix("0B")) {
+        i64::from_str_radix(bin, 2).ok()
+    } else {
+        text.parse().ok()
+    }
+}
+
+pub fn parse_enums(tokens: &[Token]) -> Vec<CEnum> {
+    let mut enums = Vec::new();
+    let mut i = 0;
+
+    while i < tokens.len() {
+        if !(tokens[i].is_ident() && tokens[i].text == "enum") {
+            i += 1;
+            continue;
+        }
+        let start = tokens[i].span.start;
+        let mut j = i + 1;
+        let mut name_span = tokens[i].span.clone();
+        let name = match tokens.get(j) {
+            Some(t) if t.is_ident() => {
+                j += 1;
+                name_span = t.span.clone();
+                Some(t.text.clone())
+            }
+            _ => None,
+        };
+        if !tokens.get(j).is_some_and(|t| t.is_punct('{')) {
+            // `enum foo bar;` style usage, not a definition.
+            i = j;
+            continue;
+        }
+        j += 1;
+
+        let mut members = Vec::new();
+        let mut end = tokens.last().map_or(start, |t| t.span.end);
+        while j < tokens.len() {
+            if tokens[j].is_punct('}') {
+                end = tokens[j].span.end;
+                j += 1;
+                break;
+            }
+            if !tokens[j].is_ident() {
+                j += 1;
+                continue;
+            }
+
+            let member = &tokens[j];
+            j += 1;
+            let mut value = None;
+            if tokens.get(j).is_some_and(|t| t.is_punct('=')) {
+                j += 1;
+                let mut parts = Vec::new();
+                let mut depth = 0;
+                while let Some(t) = tokens.get(j) {
+                    if depth == 0 && (t.is_punct(',') || t.is_punct('}')) {
+                        break;
+                    }
+                    if t.is_punct('(') {
+                        depth += 1;
+                    } else if t.is_punct(')') {
+                        depth -= 1;
+                    }
+                    parts.push(t.text.as_str());
+                    j += 1;
+                }
+                value = Some(parts.concat());
+            }
+            if tokens.get(j).is_some_and(|t| t.is_punct(',')) {
+                j += 1;
+            }
+
+            members.push(EnumMember {
+                name: member.text.clone(),
+                span: member.span.clone(),
+                value,
+            });
+        }
+
+        enums.push(CEnum {
+            name,
+            span: start..end,
+            name_span,
+            members,
+        });
+        i = j;
+    }
+
+    enums
+}
+
+/// Heuristic for the enum naming the keymap's layers: its name mentions
+/// "layer" (`enum layers`, `enum layer_names`...), or it declares one of the
+/// names used as a keymaps index.
+pub fn find_layer_enum<'a>(enums: &'a [CEnum], layer_names: &[&str]) -> Option<&'a CEnum> {
+    enums
+        .iter()
+        .find(|e| e.name.as_deref().is_some_and(|n| n.contains("layer")))
+        .or_else(|| {
+            enums
+                .iter()
+                .find(|e| e.members.iter().any(|m| layer_names.contains(&m.name.as_str())))
+        })
+}
@@ -0,0 +1,185 @@
+use crate::parser::tokens::{tokenize, Token, TokenKind};
+use std::ops::Range;
+
+/// A keycode expression as written in a keymap, e.g. `LT(_NAV, KC_SPC)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyExpr {
+    Ident(String),
+    Number(String),
+    Call { name: String, args: Vec<KeyExpr> },
+    /// Operands of a bitwise or, e.g. `MOD_LCTL | MOD_LSFT`.
+    Or(Vec<KeyExpr>),
+    /// Anything the parser doesn't understand, kept verbatim.
+    Other(String),
+}
+
+pub fn parse_key_expr(text: &str) -> KeyExpr {
+    let tokens = tokenize(text);
+    let mut pos = 0;
+    match parse_or(&tokens, &mut pos) {
+        Some(expr) if pos == tokens.len() => expr,
+        _ => KeyExpr::Other(text.split_whitespace().collect::<Vec<_>>().join(" ")),
+    }
+}
+
+fn parse_or(tokens: &[Token], pos: &mut usize) -> Option<KeyExpr> {
+    let mut operands = vec![parse_primary(tokens, pos)?];
+    while tokens.get(*pos).is_some_and(|t| t.is_punct('|')) {
+        *pos += 1;
+        operands.push(parse_primary(tokens, pos)?);
+    }
+    if operands.len() == 1 {
+        operands.pop()
+    } else {
+        Some(KeyExpr::Or(operands))
+    }
+}
+
+fn parse_primary(tokens: &[Token], pos: &mut usize) -> Option<KeyExpr> {
+    let token = tokens.get(*pos)?;
+    *pos += 1;
+    match token.kind {
+        TokenKind::Number => Some(KeyExpr::Number(token.text.clone())),
+        TokenKind::Ident => {
+            if !tokens.get(*pos).is_some_and(|t| t.is_punct('(')) {
+                return Some(KeyExpr::Ident(token.text.clone()));
+            }
+            *pos += 1;
+            let mut args = Vec::new();
+            if tokens.get(*pos).is_some_and(|t| t.is_punct(')')) {
+                *pos += 1;
+                return Some(KeyExpr::Call { name: token.text.clone(), args });
+            }
+            loop {
+                args.push(parse_or(tokens, pos)?);
+                let sep = tokens.get(*pos)?;
+                *pos += 1;
+                if sep.is_punct(')') {
+                    break;
+                }
+                if !sep.is_punct(',') {
+                    return None;
+                }
+            }
+            Some(KeyExpr::Call { name: token.text.clone(), args })
+        }
+        TokenKind::Punct if token.is_punct('(') => {
+            let inner = parse_or(tokens, pos)?;
+            if !tokens.get(*pos)?.is_punct(')') {
+                return None;
+            }
+            *pos += 1;
+            Some(inner)
+        }
+        TokenKind::Punct => None,
+    }
+}
+
+/// A call whose argument list contains a given offset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnclosingCall {
+    pub name: String,
+    pub name_span: Range<usize>,
+    /// Zero-based index of the argument containing the offset.
+    pub arg_index: usize,
+}
+
+/// Finds the innermost named call around `offset`. Plain parentheses are looked
+/// through; braces and brackets end the search, since their commas aren't arguments.
+pub fn enclosing_call(tokens: &[Token], offset: usize) -> Option<EnclosingCall> {
+    enum Frame {
+        Call(EnclosingCall),
+        Paren,
+        Block,
+    }
+
+    let mut stack: Vec<Frame> = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if token.span.end > offset {
+            break;
+        }
+        if token.kind != TokenKind::Punct {
+            continue;
+        }
+        match token.text.as_str() {
+            "(" => {
+                let frame = match i.checked_sub(1).map(|p| &tokens[p]) {
+                    Some(prev) if prev.is_ident() => Frame::Call(EnclosingCall {
+                        name: prev.text.clone(),
+                        name_span: prev.span.clone(),
+                        arg_index: 0,
+                    }),
+                    _ => Frame::Paren,
+                };
+                stack.push(frame);
+            }
+            "{" | "[" => stack.push(Frame::Block),
+            ")" | "}" | "]" => {
+                stack.pop();
+            }
+            "," => {
+                if let Some(Frame::Call(call)) = stack.last_mut() {
+                    call.arg_index += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for frame in stack.into_iter().rev() {
+        match frame {
+            Frame::Call(call) => return Some(call),
+            Frame::Paren => continue,
+            Frame::Block => return None,
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key_expr() {
+        assert_eq!(parse_key_expr("KC_A"), KeyExpr::Ident("KC_A".to_string()));
+        assert_eq!(
+            parse_key_expr("LT(_NAV, LCTL(KC_SPC))"),
+            KeyExpr::Call {
+                name: "LT".to_string(),
+                args: vec![
+                    KeyExpr::Ident("_NAV".to_string()),
+                    KeyExpr::Call { name: "LCTL".to_string(), args: vec![KeyExpr::Ident("KC_SPC".to_string())] },
+                ],
+            }
+        );
+        assert_eq!(
+            parse_key_expr("OSM(MOD_LCTL | MOD_LSFT)"),
+            KeyExpr::Call {
+                name: "OSM".to_string(),
+                args: vec![KeyExpr::Or(vec![
+                    KeyExpr::Ident("MOD_LCTL".to_string()),
+                    KeyExpr::Ident("MOD_LSFT".to_string()),
+                ])],
+            }
+        );
+        assert_eq!(parse_key_expr("LT(1,"), KeyExpr::Other("LT(1,".to_string()));
+    }
+
+    #[test]
+    fn test_enclosing_call() {
+        let content = "[0] = LAYOUT(KC_A, LT(_NAV, KC_), OSM((MOD_LCTL | ";
+        let tokens = tokenize(content);
+        let at = |needle: &str| content.find(needle).unwrap() + needle.len();
+
+        let call = enclosing_call(&tokens, at("LAYOUT(")).unwrap();
+        assert_eq!((call.name.as_str(), call.arg_index), ("LAYOUT", 0));
+        let call = enclosing_call(&tokens, at("KC_A, ")).unwrap();
+        assert_eq!((call.name.as_str(), call.arg_index), ("LAYOUT", 1));
+        let call = enclosing_call(&tokens, at("_NAV, KC_")).unwrap();
+        assert_eq!((call.name.as_str(), call.arg_index), ("LT", 1));
+        let call = enclosing_call(&tokens, content.len()).unwrap();
+        assert_eq!((call.name.as_str(), call.arg_index), ("OSM", 0));
+        assert!(enclosing_call(&tokens, 1).is_none());
+    }
+}
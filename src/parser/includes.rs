@@ -0,0 +1,23 @@
+use regex::Regex;
+use std::ops::Range;
+
+/// A quoted `#include "file.h"`; system includes (`<...>`) are never user code.
+#[derive(Debug, Clone)]
+pub struct Include {
+    pub path: String,
+    /// The path between the quotes.
+    pub span: Range<usize>,
+}
+
+pub fn parse_includes(content: &str) -> Vec<Include> {
+    let re = Regex::new(r#"(?m)^[ \t]*#[ \t]*include[ \t]*"([^"\n]+)""#).unwrap();
+    re.captures_iter(content)
+        .map(|cap| {
+            let path = cap.get(1).unwrap();
+            Include {
+                path: path.as_str().to_string(),
+                span: path.range(),
+            }
+        })
+        .collect()
+}
@@ -21,7 +21,43 @@ pub struct LayoutMap {
 pub struct LayoutKey {
     pub x: f32,
     pub y: f32,
-    // we just need the count, but parsing struct needs to match
+    #[serde(default)]
+    pub w: Option<f32>,
+    #[serde(default)]
+    pub h: Option<f32>,
+    /// Electrical position as `[row, column]`.
+    #[serde(default)]
+    pub matrix: Option<[u32; 2]>,
+    /// The legend printed on the physical key.
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+/// The physical row of each key, numbered top to bottom. Keys are grouped by
+/// `y` rounded to whole units, so split layouts listing one half after the
+/// other still put both halves of a row together.
+pub fn key_rows(layout: &[LayoutKey]) -> Vec<usize> {
+    let row_y = |key: &LayoutKey| key.y.round() as i64;
+    let mut ys: Vec<i64> = layout.iter().map(row_y).collect();
+    ys.sort_unstable();
+    ys.dedup();
+    layout
+        .iter()
+        .map(|key| ys.binary_search(&row_y(key)).unwrap_or_default())
+        .collect()
+}
+
+/// Key indices of each physical row, top to bottom, every row ordered by `x`.
+pub fn layout_rows(layout: &[LayoutKey]) -> Vec<Vec<usize>> {
+    let rows = key_rows(layout);
+    let mut grouped = vec![Vec::new(); rows.iter().max().map_or(0, |r| r + 1)];
+    for (i, row) in rows.iter().enumerate() {
+        grouped[*row].push(i);
+    }
+    for row in &mut grouped {
+        row.sort_by(|&a, &b| layout[a].x.total_cmp(&layout[b].x));
+    }
+    grouped
 }
 
 pub fn find_info_json_path(start_path: &Path) -> Option<std::path::PathBuf> {
@@ -49,10 +85,10 @@ pub fn find_info_json_path(start_path: &Path) -> Option<std::path::PathBuf> {
 }
 
 pub fn find_and_load_info_json(start_path: &Path) -> Option<KeyboardInfo> {
-    if let Some(path) = find_info_json_path(start_path) {
-        if let Ok(info) = load_info_json(&path) {
-            return Some(info);
-        }
+    if let Some(path) = find_info_json_path(start_path)
+        && let Ok(info) = load_info_json(&path)
+    {
+        return Some(info);
     }
     None
 }
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
 
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -8,6 +10,46 @@ pub struct Keycode {
     pub description: String,
     pub doc: String,
     pub category: String,
+    /// Compact label for drawing keymaps, e.g. "⌫" for `KC_BSPC`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub legend: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<Deprecation>,
+}
+
+/// Marks a keycode that QMK renamed or removed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Deprecation {
+    /// Current names, best match first. The first one is used for bulk migrations.
+    pub replacements: Vec<String>,
+}
+
+/// Categories outside the 8-bit basic keycode range.
+const NON_BASIC_CATEGORIES: &[&str] = &["shifted", "quantum", "lighting"];
+
+const TRANSPARENT_KEYCODES: &[&str] = &["_______", "KC_TRNS", "KC_TRANSPARENT"];
+const NOOP_KEYCODES: &[&str] = &["XXXXXXX", "KC_NO"];
+
+impl Keycode {
+    /// Whether the keycode fits the tap argument of mod-tap and layer-tap keys.
+    pub fn is_basic(&self) -> bool {
+        self.deprecated.is_none() && !NON_BASIC_CATEGORIES.contains(&self.category.as_str())
+    }
+
+    /// Whether the key falls through to the layer below.
+    pub fn is_transparent(&self) -> bool {
+        TRANSPARENT_KEYCODES.contains(&self.name.as_str())
+    }
+
+    /// Whether the key does nothing at all.
+    pub fn is_noop(&self) -> bool {
+        NOOP_KEYCODES.contains(&self.name.as_str())
+    }
+
+    /// The keycode this one is a short name for, e.g. `KC_ENTER` for `KC_ENT`.
+    pub fn alias_of(&self) -> Option<&str> {
+        self.doc.strip_prefix("Alias for ").map(str::trim)
+    }
 }
 
 pub type KeycodeMap = HashMap<String, Keycode>;
@@ -22,3 +64,10 @@ pub fn load_keycodes() -> KeycodeMap {
     }
     map
 }
+
+/// Reads a keycode list in the same schema as `data/keycodes.json`.
+pub fn load_keycodes_file(path: &Path) -> Result<Vec<Keycode>, Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(path)?;
+    let keycodes: Vec<Keycode> = serde_json::from_str(&content)?;
+    Ok(keycodes)
+}
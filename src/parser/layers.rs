@@ -3,19 +3,30 @@ use regex::Regex;
 #[derive(Debug, Clone)]
 pub struct Layer {
     pub name: String,
+    /// Span of the name inside the designator brackets.
+    pub name_span: std::ops::Range<usize>,
     pub macro_name: String,
     pub key_count: usize,
     pub span: std::ops::Range<usize>,
+    /// Span of each key argument, without surrounding whitespace or comments.
+    pub keys: Vec<std::ops::Range<usize>>,
 }
 
 pub fn parse_layers(content: &str) -> Vec<Layer> {
     // Regex to find the start of a layer definition: [LAYER_NAME] = LAYOUT_MACRO(
     // We capture the layer name and the macro name.
-    let re = Regex::new(r"\[(?P<layer>[a-zA-Z0-9_]+)\]\s*=\s*(?P<macro>[a-zA-Z0-9_]+)\s*\(").unwrap();
+    let re = Regex::new(r"\[\s*(?P<layer>[a-zA-Z0-9_]+)\s*\]\s*=\s*(?P<macro>[a-zA-Z0-9_]+)\s*\(").unwrap();
     
     let mut layers = Vec::new();
 
-    for cap in re.captures_iter(content) {
+    // Designated initializers elsewhere (tap dances, combos...) look the same,
+    // so only search inside the keymaps array when there is one.
+    let scope = keymaps_span(content).unwrap_or(0..content.len());
+
+    for cap in re.captures_iter(&content[..scope.end]) {
+        if cap.get(0).unwrap().start() < scope.start {
+            continue;
+        }
         let layer_name = cap["layer"].to_string();
         let macro_name = cap["macro"].to_string();
         let match_start = cap.get(0).unwrap().start();
@@ -26,66 +37,116 @@ pub fn parse_layers(content: &str) -> Vec<Layer> {
         // Slice the content starting from after the opening '('
         let remaining = &content[match_end..];
         
-        // Count keys by balancing parentheses/braces/brackets and counting commas
-        let (key_count, consumed) = count_keys_in_macro(remaining);
+        // Split keys by balancing parentheses/braces/brackets and splitting on commas
+        let (keys, consumed) = split_keys_in_macro(remaining);
         
         let total_end = match_end + consumed;
 
         layers.push(Layer {
             name: layer_name,
+            name_span: cap.name("layer").unwrap().range(),
             macro_name,
-            key_count,
+            key_count: keys.len(),
             span: match_start..total_end,
+            keys: keys.into_iter().map(|k| match_end + k.start..match_end + k.end).collect(),
         });
     }
     
     layers
 }
 
-/// Counts the number of comma-separated arguments at the top level of the current scope,
-/// stopping at a closing parenthesis ')'.
-/// Returns (number of items, bytes consumed)
-fn count_keys_in_macro(text: &str) -> (usize, usize) {
+/// Byte range of the `keymaps[][MATRIX_ROWS][MATRIX_COLS] = { ... }` initializer braces.
+pub fn keymaps_span(content: &str) -> Option<std::ops::Range<usize>> {
+    let re = Regex::new(r"\bkeymaps\s*\[[^=;]*=\s*\{").unwrap();
+    let m = re.find(content)?;
+    let open = m.end() - 1;
+
     let mut depth = 0;
-    let mut key_count = 0;
-    let mut has_content = false;
-    let mut chars = text.char_indices();
-    let mut last_idx = 0;
-    
-    // If the layout is empty "LAYOUT()"
-    // we need to handle that.
-    
-    while let Some((idx, c)) = chars.next() {
-        last_idx = idx;
+    for (idx, c) in content[open..].char_indices() {
         match c {
-            '(' | '{' | '[' => {
-                depth += 1;
-                has_content = true;
-            }
-            ')' | '}' | ']' => {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
                 if depth == 0 {
-                    // We found the closing parenthesis of the LAYOUT macro itself
-                    if has_content {
-                        key_count += 1;
-                    }
-                    return (key_count, idx + 1);
+                    return Some(open..open + idx + 1);
                 }
-                depth -= 1;
             }
-            ',' => {
-                if depth == 0 {
-                    key_count += 1;
-                    has_content = false; // reset for next item
+            _ => {}
+        }
+    }
+    Some(open..content.len())
+}
+
+/// Byte range of the whole `keymaps` declaration, from the start of its first
+/// line (`const uint16_t PROGMEM keymaps...`) to the terminating semicolon.
+pub fn keymaps_declaration_span(content: &str) -> Option<std::ops::Range<usize>> {
+    let braces = keymaps_span(content)?;
+    let name = content[..braces.start].rfind("keymaps")?;
+    let line_start = content[..name].rfind('\n').map_or(0, |i| i + 1);
+    let start = line_start + (content[line_start..name].len() - content[line_start..name].trim_start().len());
+    let after = &content[braces.end..];
+    let end = match after.trim_start().strip_prefix(';') {
+        Some(_) => braces.end + after.len() - after.trim_start().len() + 1,
+        None => braces.end,
+    };
+    Some(start..end)
+}
+
+/// Splits the comma-separated arguments at the top level of the current scope,
+/// stopping at a closing parenthesis ')'. Comments between keys are skipped.
+/// Returns (span of each item relative to `text`, bytes consumed)
+fn split_keys_in_macro(text: &str) -> (Vec<std::ops::Range<usize>>, usize) {
+    let bytes = text.as_bytes();
+    let mut depth = 0;
+    let mut keys = Vec::new();
+    // Span of the current item's non-whitespace content, if it has any yet.
+    let mut current: Option<std::ops::Range<usize>> = None;
+    let mut idx = 0;
+
+    while idx < bytes.len() {
+        let c = bytes[idx];
+        match c {
+            b'/' if bytes.get(idx + 1) == Some(&b'/') => {
+                while idx < bytes.len() && bytes[idx] != b'\n' {
+                    idx += 1;
                 }
+                continue;
             }
-            c if !c.is_whitespace() => {
-                 has_content = true;
+            b'/' if bytes.get(idx + 1) == Some(&b'*') => {
+                idx += 2;
+                while idx < bytes.len() && !(bytes[idx] == b'*' && bytes.get(idx + 1) == Some(&b'/')) {
+                    idx += 1;
+                }
+                idx = (idx + 2).min(bytes.len());
+                continue;
+            }
+            b')' | b'}' | b']' if depth == 0 => {
+                // We found the closing parenthesis of the LAYOUT macro itself
+                keys.extend(current.take());
+                return (keys, idx + 1);
+            }
+            b',' if depth == 0 => {
+                keys.extend(current.take());
+            }
+            _ => {
+                if matches!(c, b'(' | b'{' | b'[') {
+                    depth += 1;
+                } else if matches!(c, b')' | b'}' | b']') {
+                    depth -= 1;
+                }
+                if !c.is_ascii_whitespace() {
+                    match &mut current {
+                        Some(span) => span.end = idx + 1,
+                        None => current = Some(idx..idx + 1),
+                    }
+                }
             }
-            _ => {}
         }
+        idx += 1;
     }
-    
-    (key_count, last_idx)
+
+    keys.extend(current);
+    (keys, text.len())
 }
 
 
@@ -135,6 +196,41 @@ mod tests {
          assert_eq!(layers[0].key_count, 4);
     }
 
+    #[test]
+    fn test_parse_layers_ignores_other_tables() {
+        let content = "
+            tap_dance_action_t tap_dance_actions[] = {
+                [TD_ESC] = ACTION_TAP_DANCE_DOUBLE(KC_ESC, KC_CAPS),
+            };
+            const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {
+                [_BASE] = LAYOUT(KC_A, TD(TD_ESC))
+            };
+        ";
+        let layers = parse_layers(content);
+        assert_eq!(layers.len(), 1);
+        assert_eq!(layers[0].name, "_BASE");
+        assert!(keymaps_span(content).unwrap().contains(&layers[0].span.start));
+    }
+
+    #[test]
+    fn test_parse_layers_key_spans() {
+        let content = "[0] = LAYOUT( KC_A, /* a, b */ LT(1, KC_B) , // KC_C,\n KC_D )";
+        let layers = parse_layers(content);
+        let keys: Vec<&str> = layers[0].keys.iter().map(|k| &content[k.clone()]).collect();
+        assert_eq!(keys, vec!["KC_A", "LT(1, KC_B)", "KC_D"]);
+        assert_eq!(layers[0].key_count, 3);
+        assert!(content[layers[0].span.clone()].ends_with(')'));
+    }
+
+    #[test]
+    fn test_parse_layers_name_span() {
+        let content = "[_BASE] = LAYOUT(KC_A),\n    [ _NAV ] = LAYOUT(KC_B)";
+        let layers = parse_layers(content);
+        let names: Vec<&str> = layers.iter().map(|l| &content[l.name_span.clone()]).collect();
+        assert_eq!(names, vec!["_BASE", "_NAV"]);
+        assert_eq!(layers[1].name, "_NAV");
+    }
+
     #[test]
     fn test_parse_layers_empty() {
         let content = "[3] = LAYOUT()";
@@ -1,5 +1,10 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+/// Highest layer index addressable by the 5-bit layer field of most layer keycodes.
+pub const MAX_LAYER: u32 = 31;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Macro {
@@ -7,13 +12,89 @@ pub struct Macro {
     pub detail: String,
     pub description: String,
     pub doc: String,
+    /// Legend template where `{param}` is replaced by the legend of that argument,
+    /// e.g. "{kc} / {layer}" for `LT`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub legend: Option<String>,
+    #[serde(default)]
+    pub params: Vec<MacroParam>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MacroParam {
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: ParamKind,
+    /// Text pre-filled by snippet completions; defaults to the parameter name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub placeholder: Option<String>,
+}
+
+/// What a macro argument is allowed to be.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ParamKind {
+    /// A layer index or layer name, up to `max` (inclusive).
+    Layer {
+        #[serde(default = "default_max_layer")]
+        max: u32,
+    },
+    /// A basic keycode (`KC_A`..`KC_RGUI`), as required by mod-tap and layer-tap keys.
+    BasicKeycode,
+    /// Any keycode, including other functional keycodes.
+    Keycode,
+    /// A `MOD_*` bitmask.
+    ModMask,
+    /// An index into `tap_dance_actions`.
+    TapDance,
+    /// An integer in `min..=max`.
+    Int { min: i64, max: i64 },
+}
+
+fn default_max_layer() -> u32 {
+    MAX_LAYER
+}
+
+impl ParamKind {
+    /// Short human readable description, e.g. "layer 0–15".
+    pub fn describe(&self) -> String {
+        match self {
+            ParamKind::Layer { max } => format!("layer 0–{}", max),
+            ParamKind::BasicKeycode => "basic keycode".to_string(),
+            ParamKind::Keycode => "keycode".to_string(),
+            ParamKind::ModMask => "mod mask".to_string(),
+            ParamKind::TapDance => "tap dance index".to_string(),
+            ParamKind::Int { min, max } => format!("integer {}–{}", min, max),
+        }
+    }
+}
+
+impl Macro {
+    /// Call signature built from the parameter list, e.g. `LT(layer, kc)`.
+    pub fn signature(&self) -> String {
+        let params: Vec<&str> = self.params.iter().map(|p| p.name.as_str()).collect();
+        format!("{}({})", self.name, params.join(", "))
+    }
 }
 
 pub type MacroMap = HashMap<String, Macro>;
 
+/// A `MOD_*` constant accepted by `ParamKind::ModMask` parameters.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModMask {
+    pub name: String,
+    pub description: String,
+    pub doc: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub legend: Option<String>,
+}
+
+pub type ModMaskMap = HashMap<String, ModMask>;
+
 pub fn load_macros() -> MacroMap {
     let data = include_str!("../../data/macros.json");
     let macros: Vec<Macro> = serde_json::from_str(data).expect("Failed to parse macros.json");
+    validate_macros(&macros).expect("Invalid macros.json");
     
     let mut map = HashMap::new();
     for m in macros {
@@ -21,3 +102,63 @@ pub fn load_macros() -> MacroMap {
     }
     map
 }
+
+pub fn load_mod_masks() -> ModMaskMap {
+    let data = include_str!("../../data/mod_masks.json");
+    let masks: Vec<ModMask> = serde_json::from_str(data).expect("Failed to parse mod_masks.json");
+
+    let mut map = HashMap::new();
+    for m in masks {
+        map.insert(m.name.clone(), m);
+    }
+    map
+}
+
+/// Reads a macro list in the same schema as `data/macros.json`.
+pub fn load_macros_file(path: &Path) -> Result<Vec<Macro>, Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(path)?;
+    let macros: Vec<Macro> = serde_json::from_str(&content)?;
+    validate_macros(&macros)?;
+    Ok(macros)
+}
+
+/// Checks the invariants serde can't express: unique names and sensible ranges.
+pub fn validate_macros(macros: &[Macro]) -> Result<(), String> {
+    let mut names = HashSet::new();
+    for m in macros {
+        if m.name.is_empty() {
+            return Err("macro with an empty name".to_string());
+        }
+        if !names.insert(m.name.as_str()) {
+            return Err(format!("duplicate macro '{}'", m.name));
+        }
+
+        let mut params = HashSet::new();
+        for param in &m.params {
+            if param.name.is_empty() {
+                return Err(format!("{}: parameter with an empty name", m.name));
+            }
+            if !params.insert(param.name.as_str()) {
+                return Err(format!("{}: duplicate parameter '{}'", m.name, param.name));
+            }
+            match param.kind {
+                ParamKind::Layer { max } if max > MAX_LAYER => {
+                    return Err(format!("{}: layer parameter '{}' allows up to {}, QMK supports {}", m.name, param.name, max, MAX_LAYER));
+                }
+                ParamKind::Int { min, max } if min > max => {
+                    return Err(format!("{}: parameter '{}' has an empty range {}..{}", m.name, param.name, min, max));
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(legend) = &m.legend {
+            for placeholder in legend.split('{').skip(1).filter_map(|rest| rest.split_once('}')).map(|(name, _)| name) {
+                if !params.contains(placeholder) {
+                    return Err(format!("{}: legend refers to unknown parameter '{}'", m.name, placeholder));
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -3,3 +3,10 @@ pub mod macros;
 pub mod layers;
 pub mod custom;
 pub mod info_json;
+pub mod overlay;
+pub mod tokens;
+pub mod expr;
+pub mod tables;
+pub mod enums;
+pub mod includes;
+pub mod defines;
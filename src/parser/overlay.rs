@@ -0,0 +1,129 @@
+use crate::parser::keycodes::{load_keycodes_file, KeycodeMap};
+use crate::parser::macros::{load_macros_file, MacroMap};
+use dashmap::DashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Directory holding user supplied additions to the built-in databases.
+pub const OVERLAY_DIR: &str = ".qmk-lsp";
+pub const KEYCODES_OVERLAY: &str = "keycodes.json";
+pub const MACROS_OVERLAY: &str = "macros.json";
+
+/// Keycode and macro databases as seen by a single document.
+pub struct Overlays {
+    /// The `.qmk-lsp` directories merged, outermost first.
+    pub dirs: Vec<PathBuf>,
+    pub keycodes: Arc<KeycodeMap>,
+    pub macros: Arc<MacroMap>,
+    pub errors: Vec<String>,
+}
+
+/// Merged overlays shared by every document under the same `.qmk-lsp` directories,
+/// so re-parsing a document never touches the filesystem. Cleared whenever an
+/// overlay file changes.
+#[derive(Default)]
+pub struct OverlayCache {
+    /// The overlay directories applying to each document directory.
+    dirs: DashMap<PathBuf, Vec<PathBuf>>,
+    merged: DashMap<Vec<PathBuf>, Arc<Overlays>>,
+}
+
+impl OverlayCache {
+    /// The overlays for the document at `path`, and whether they were loaded by this
+    /// call rather than found in the cache.
+    pub fn get(&self, path: &Path, keycodes: &Arc<KeycodeMap>, macros: &Arc<MacroMap>) -> (Arc<Overlays>, bool) {
+        let dir = path.parent().unwrap_or(path).to_path_buf();
+        let dirs = self
+            .dirs
+            .entry(dir.clone())
+            .or_insert_with(|| find_overlay_dirs(&dir))
+            .clone();
+        if let Some(overlays) = self.merged.get(&dirs) {
+            return (overlays.clone(), false);
+        }
+        let overlays = Arc::new(merge_overlays(dirs.clone(), keycodes, macros));
+        self.merged.insert(dirs, overlays.clone());
+        (overlays, true)
+    }
+
+    pub fn clear(&self) {
+        self.dirs.clear();
+        self.merged.clear();
+    }
+}
+
+/// Collects every `.qmk-lsp` directory between the filesystem root and `start_path`,
+/// outermost first, so that directories closer to the keymap win when merging.
+pub fn find_overlay_dirs(start_path: &Path) -> Vec<PathBuf> {
+    let mut current = start_path.to_path_buf();
+    if current.is_file() {
+        current.pop();
+    }
+
+    let mut dirs = Vec::new();
+    loop {
+        let dir = current.join(OVERLAY_DIR);
+        if dir.is_dir() {
+            dirs.push(dir);
+        }
+        if !current.pop() {
+            break;
+        }
+    }
+    dirs.reverse();
+    dirs
+}
+
+pub fn is_overlay_file(path: &Path) -> bool {
+    path.parent()
+        .and_then(|p| p.file_name())
+        .is_some_and(|name| name == OVERLAY_DIR)
+}
+
+/// Merges the overlay files found above `start_path` into the built-in maps.
+/// The built-in maps are shared as-is when no overlay applies.
+pub fn load_overlays(start_path: &Path, keycodes: &Arc<KeycodeMap>, macros: &Arc<MacroMap>) -> Overlays {
+    merge_overlays(find_overlay_dirs(start_path), keycodes, macros)
+}
+
+/// Merges the overlay files of `dirs`, outermost first, into the built-in maps.
+pub fn merge_overlays(dirs: Vec<PathBuf>, keycodes: &Arc<KeycodeMap>, macros: &Arc<MacroMap>) -> Overlays {
+    let mut merged_keycodes: Option<KeycodeMap> = None;
+    let mut merged_macros: Option<MacroMap> = None;
+    let mut errors = Vec::new();
+
+    for dir in &dirs {
+        let keycodes_path = dir.join(KEYCODES_OVERLAY);
+        if keycodes_path.exists() {
+            match load_keycodes_file(&keycodes_path) {
+                Ok(extra) => {
+                    let map = merged_keycodes.get_or_insert_with(|| (**keycodes).clone());
+                    for kc in extra {
+                        map.insert(kc.name.clone(), kc);
+                    }
+                }
+                Err(e) => errors.push(format!("{}: {}", keycodes_path.display(), e)),
+            }
+        }
+
+        let macros_path = dir.join(MACROS_OVERLAY);
+        if macros_path.exists() {
+            match load_macros_file(&macros_path) {
+                Ok(extra) => {
+                    let map = merged_macros.get_or_insert_with(|| (**macros).clone());
+                    for m in extra {
+                        map.insert(m.name.clone(), m);
+                    }
+                }
+                Err(e) => errors.push(format!("{}: {}", macros_path.display(), e)),
+            }
+        }
+    }
+
+    Overlays {
+        dirs,
+        keycodes: merged_keycodes.map(Arc::new).unwrap_or_else(|| keycodes.clone()),
+        macros: merged_macros.map(Arc::new).unwrap_or_else(|| macros.clone()),
+        errors,
+    }
+}
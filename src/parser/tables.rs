@@ -0,0 +1,95 @@
+use crate::parser::tokens::Token;
+use std::ops::Range;
+
+pub const TAP_DANCE_TABLE: &str = "tap_dance_actions";
+pub const COMBO_TABLE: &str = "key_combos";
+
+/// A feature table such as `tap_dance_actions[] = { ... }`.
+#[derive(Debug, Clone)]
+pub struct Table {
+    pub name: String,
+    /// From the table name to the closing brace.
+    pub span: Range<usize>,
+    pub entries: Vec<TableEntry>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TableEntry {
+    /// The designated index (`[TD_ESC_CAPS] = ...`), the combo name of a
+    /// `COMBO(name, kc)` entry, or the position (`[2]`) for anything else.
+    pub name: String,
+    pub name_span: Range<usize>,
+    pub span: Range<usize>,
+}
+
+/// Parses the initializer of the array called `table_name`.
+pub fn parse_table(tokens: &[Token], table_name: &str) -> Option<Table> {
+    let (start, open) = tokens
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| t.is_ident() && t.text == table_name)
+        .find_map(|(start, _)| initializer_open(tokens, start).map(|open| (start, open)))?;
+
+    let mut entries = Vec::new();
+    let mut depth = 0;
+    let mut entry_start = open + 1;
+    let mut end = tokens.last()?.span.end;
+    for i in open..tokens.len() {
+        let token = &tokens[i];
+        if token.is_punct('{') || token.is_punct('(') || token.is_punct('[') {
+            depth += 1;
+        } else if token.is_punct('}') || token.is_punct(')') || token.is_punct(']') {
+            depth -= 1;
+            if depth == 0 {
+                push_entry(&tokens[entry_start..i], &mut entries);
+                end = token.span.end;
+                break;
+            }
+        } else if depth == 1 && token.is_punct(',') {
+            push_entry(&tokens[entry_start..i], &mut entries);
+            entry_start = i + 1;
+        }
+    }
+
+    Some(Table {
+        name: table_name.to_string(),
+        span: tokens[start].span.start..end,
+        entries,
+    })
+}
+
+/// Index of the `{` opening the initializer of the declaration at `start`.
+/// A bare `extern` declaration has none.
+fn initializer_open(tokens: &[Token], start: usize) -> Option<usize> {
+    for (i, token) in tokens.iter().enumerate().skip(start + 1) {
+        if token.is_punct('{') {
+            return Some(i);
+        }
+        if token.is_punct(';') {
+            break;
+        }
+    }
+    None
+}
+
+fn push_entry(tokens: &[Token], entries: &mut Vec<TableEntry>) {
+    let (Some(first), Some(last)) = (tokens.first(), tokens.last()) else {
+        return;
+    };
+    let span = first.span.start..last.span.end;
+
+    let designated = match tokens {
+        [open, name, close, ..] if open.is_punct('[') && name.is_ident() && close.is_punct(']') => Some(name),
+        _ => None,
+    };
+    let combo = match tokens {
+        [callee, open, name, ..] if callee.text.starts_with("COMBO") && open.is_punct('(') && name.is_ident() => Some(name),
+        _ => None,
+    };
+
+    let (name, name_span) = match designated.or(combo) {
+        Some(token) => (token.text.clone(), token.span.clone()),
+        None => (format!("[{}]", entries.len()), span.clone()),
+    };
+    entries.push(TableEntry { name, name_span, span });
+}
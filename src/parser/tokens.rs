@@ -0,0 +1,141 @@
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Ident,
+    Number,
+    Punct,
+}
+
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub text: String,
+    pub span: Range<usize>,
+}
+
+impl Token {
+    pub fn is_ident(&self) -> bool {
+        self.kind == TokenKind::Ident
+    }
+
+    pub fn is_punct(&self, c: char) -> bool {
+        self.kind == TokenKind::Punct && self.text.len() == 1 && self.text.starts_with(c)
+    }
+}
+
+/// Splits C source into identifiers, numbers and punctuation.
+/// Comments, string literals and character literals are skipped entirely, so
+/// every identifier returned is a real code reference.
+pub fn tokenize(content: &str) -> Vec<Token> {
+    let bytes = content.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let b = bytes[i];
+        match b {
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i += 2;
+                while i < bytes.len() && !(bytes[i] == b'*' && bytes.get(i + 1) == Some(&b'/')) {
+                    i += 1;
+                }
+                i = (i + 2).min(bytes.len());
+            }
+            b'"' | b'\'' => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != b && bytes[i] != b'\n' {
+                    if bytes[i] == b'\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                i = (i + 1).min(bytes.len());
+            }
+            b if b.is_ascii_alphabetic() || b == b'_' => {
+                let start = i;
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                    i += 1;
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Ident,
+                    text: content[start..i].to_string(),
+                    span: start..i,
+                });
+            }
+            b if b.is_ascii_digit() => {
+                let start = i;
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_' || bytes[i] == b'.') {
+                    i += 1;
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Number,
+                    text: content[start..i].to_string(),
+                    span: start..i,
+                });
+            }
+            b if b.is_ascii_whitespace() => {
+                i += 1;
+            }
+            _ => {
+                // Keep multi-byte characters whole so spans stay on char boundaries.
+                let len = content[i..].chars().next().map_or(1, |c| c.len_utf8());
+                tokens.push(Token {
+                    kind: TokenKind::Punct,
+                    text: content[i..i + len].to_string(),
+                    span: i..i + len,
+                });
+                i += len;
+            }
+        }
+    }
+
+    tokens
+}
+
+/// Index of the token containing or ending at `offset`, preferring identifiers.
+pub fn token_at(tokens: &[Token], offset: usize) -> Option<usize> {
+    let idx = tokens.partition_point(|t| t.span.end < offset);
+    let mut best = None;
+    for (i, token) in tokens.iter().enumerate().skip(idx).take(2) {
+        if token.span.start <= offset && offset <= token.span.end {
+            if token.is_ident() {
+                return Some(i);
+            }
+            best.get_or_insert(i);
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize_skips_comments_and_strings() {
+        let content = "MO(_NAV), // KC_A\n/* KC_B */ SEND_STRING(\"KC_C\"); x = 'K';";
+        let idents: Vec<String> = tokenize(content)
+            .into_iter()
+            .filter(|t| t.is_ident())
+            .map(|t| t.text)
+            .collect();
+        assert_eq!(idents, vec!["MO", "_NAV", "SEND_STRING", "x"]);
+    }
+
+    #[test]
+    fn test_token_at() {
+        let content = "LT(_NAV, KC_SPC)";
+        let tokens = tokenize(content);
+        assert_eq!(tokens[token_at(&tokens, 0).unwrap()].text, "LT");
+        assert_eq!(tokens[token_at(&tokens, 2).unwrap()].text, "LT");
+        assert_eq!(tokens[token_at(&tokens, 5).unwrap()].text, "_NAV");
+        assert_eq!(tokens[token_at(&tokens, 8).unwrap()].text, ",");
+        assert!(token_at(&tokens, 100).is_none());
+    }
+}
@@ -1,34 +1,540 @@
 use crate::parser::keycodes::{load_keycodes, KeycodeMap};
-use crate::parser::macros::{load_macros, MacroMap};
-use crate::document::{DocumentStore, DocumentData};
+use crate::parser::macros::{load_macros, load_mod_masks, MacroMap, ModMaskMap};
+use crate::config::Settings;
+use crate::document::{DocumentStore, DocumentData, LineIndex};
+use crate::firmware::{is_firmware_root, FirmwareSource};
 use crate::parser::layers::parse_layers;
-use crate::parser::custom::parse_custom_keycodes;
-use crate::parser::info_json::find_and_load_info_json;
-use regex::Regex;
+use crate::parser::custom::custom_keycodes_from_enums;
+use crate::legend::LegendContext;
+use crate::parser::info_json::{find_and_load_info_json, find_info_json_path, KeyboardInfo};
+use crate::parser::overlay::{is_overlay_file, OverlayCache};
+use crate::parser::enums::parse_enums;
+use crate::parser::tables::{parse_table, COMBO_TABLE, TAP_DANCE_TABLE};
+use crate::parser::tokens::tokenize;
+use crate::features::completion::{self, CompletionCache};
+use crate::features::references::{self, Declarations, SourceFile};
+use crate::features::code_lens::{self, InfoJson};
+use crate::features::{declare_keycode, definition, document_highlight, folding_ranges, format, inlay_hints, layout_fixes, migrate, rename, selection_ranges, semantic_tokens, signature_help, symbols, typos, workspace_symbols};
+use crate::parser::defines::parse_defines;
+use crate::parser::includes::parse_includes;
+use crate::parser::tokens::token_at;
+use crate::workspace::{
+    is_info_json, is_keymap_source, key_usage, keymap_sources, resolve_include, KeyUsage,
+    WorkspaceIndex,
+};
+use dashmap::DashMap;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use tokio::sync::OnceCell;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::*;
+use tower_lsp::lsp_types::request::{GotoImplementationParams, GotoImplementationResponse};
 use tower_lsp::{Client, LanguageServer};
 
 pub struct Backend {
     pub client: Client,
-    pub keycodes: KeycodeMap,
-    pub macros: MacroMap,
+    pub keycodes: Arc<KeycodeMap>,
+    pub macros: Arc<MacroMap>,
+    pub mod_masks: ModMaskMap,
+    pub completion_cache: CompletionCache,
+    /// Completion lists of documents with overlays, by overlay directories.
+    pub overlay_completion_caches: DashMap<Vec<PathBuf>, Arc<CompletionCache>>,
     pub documents: DocumentStore,
+    pub overlays: OverlayCache,
+    pub client_capabilities: RwLock<ClientCapabilities>,
+    pub workspace_roots: RwLock<Vec<PathBuf>>,
+    /// Built in the background once the client is initialized.
+    pub workspace_index: Arc<OnceCell<RwLock<WorkspaceIndex>>>,
+    /// Key usage across the workspace: open buffers as edited, other keymaps as indexed.
+    pub key_usage: Arc<RwLock<KeyUsage>>,
+    pub settings: RwLock<Settings>,
+    /// The `qmk_firmware` checkout built-in symbols resolve into, if one was found.
+    pub firmware: RwLock<Option<Arc<FirmwareSource>>>,
+}
+
+async fn build_index(roots: Vec<PathBuf>, key_usage: Arc<RwLock<KeyUsage>>) -> RwLock<WorkspaceIndex> {
+    let mut index = tokio::task::spawn_blocking(move || WorkspaceIndex::build(&roots))
+        .await
+        .unwrap_or_default();
+    // Buffers opened during the build are already counted from their contents.
+    key_usage.write().unwrap().merge_missing(std::mem::take(&mut index.usage));
+    RwLock::new(index)
+}
+
+/// `Backend::source_files`, given the open buffers; reads the other files from disk.
+fn read_source_files(uri: &Url, open: &HashMap<Url, String>, roots: &[PathBuf]) -> Vec<SourceFile> {
+    let Some(content) = open.get(uri).cloned() else {
+        return Vec::new();
+    };
+    let mut files = vec![SourceFile::new(uri.clone(), content)];
+    let mut seen = vec![uri.clone()];
+
+    let mut next = 0;
+    while let Some(file) = files.get(next) {
+        next += 1;
+        let Ok(path) = file.uri.to_file_path() else {
+            continue;
+        };
+        let included: Vec<PathBuf> = parse_includes(&file.content)
+            .iter()
+            .filter_map(|include| resolve_include(&path, &include.path, roots))
+            .flat_map(|header| {
+                let source = header.with_extension("c");
+                std::iter::once(header).chain(source.is_file().then_some(source))
+            })
+            .collect();
+        for path in included {
+            let Ok(file_uri) = Url::from_file_path(&path) else {
+                continue;
+            };
+            if seen.contains(&file_uri) {
+                continue;
+            }
+            seen.push(file_uri.clone());
+            let content = match open.get(&file_uri) {
+                Some(content) => content.clone(),
+                None => match std::fs::read_to_string(&path) {
+                    Ok(content) => content,
+                    Err(_) => continue,
+                },
+            };
+            files.push(SourceFile::new(file_uri, content));
+        }
+    }
+    files
 }
 
 impl Backend {
     pub fn new(client: Client) -> Self {
+        let keycodes = Arc::new(load_keycodes());
+        let macros = Arc::new(load_macros());
+        let mod_masks = load_mod_masks();
+        let completion_cache = CompletionCache::new(&keycodes, &macros, &mod_masks);
         Self {
             client,
-            keycodes: load_keycodes(),
-            macros: load_macros(),
+            keycodes,
+            macros,
+            mod_masks,
+            completion_cache,
+            overlay_completion_caches: DashMap::new(),
             documents: DocumentStore::new(),
+            overlays: OverlayCache::default(),
+            client_capabilities: RwLock::new(ClientCapabilities::default()),
+            workspace_roots: RwLock::new(Vec::new()),
+            workspace_index: Arc::new(OnceCell::new()),
+            key_usage: Arc::new(RwLock::new(KeyUsage::default())),
+            settings: RwLock::new(Settings::default()),
+            firmware: RwLock::new(None),
+        }
+    }
+
+    /// Applies new settings, switching to (and indexing in the background) the firmware
+    /// checkout they point at. Without a configured path, `QMK_HOME` or a workspace
+    /// root that is itself a checkout is used.
+    async fn configure(&self, settings: Settings) {
+        if let Some(path) = &settings.firmware_path
+            && !is_firmware_root(path)
+        {
+            self.client
+                .log_message(MessageType::WARNING, format!("{} is not a qmk_firmware checkout", path.display()))
+                .await;
+        }
+        let roots = self.workspace_roots.read().unwrap().clone();
+        let root = settings
+            .firmware_path
+            .iter()
+            .cloned()
+            .chain(std::env::var_os("QMK_HOME").map(PathBuf::from))
+            .chain(roots)
+            .find(|path| is_firmware_root(path));
+        *self.settings.write().unwrap() = settings;
+
+        let mut firmware = self.firmware.write().unwrap();
+        if firmware.as_ref().map(|f| &f.root) == root.as_ref() {
+            return;
+        }
+        *firmware = root.map(|root| {
+            let source = Arc::new(FirmwareSource::new(root));
+            let warm = source.clone();
+            tokio::spawn(async move {
+                warm.index().await;
+            });
+            source
+        });
+    }
+
+    /// The workspace index, waiting for the background build if it hasn't finished.
+    async fn workspace_index(&self) -> &RwLock<WorkspaceIndex> {
+        let roots = self.workspace_roots.read().unwrap().clone();
+        self.workspace_index
+            .get_or_init(|| build_index(roots, self.key_usage.clone()))
+            .await
+    }
+
+    fn supports_snippets(&self) -> bool {
+        self.client_capabilities
+            .read()
+            .unwrap()
+            .text_document
+            .as_ref()
+            .and_then(|t| t.completion.as_ref())
+            .and_then(|c| c.completion_item.as_ref())
+            .and_then(|i| i.snippet_support)
+            .unwrap_or(false)
+    }
+
+    /// Parses `content`, resolves the overlays that apply to `uri` and stores the result.
+    async fn update_document(&self, uri: Url, content: String) {
+        let layers = parse_layers(&content);
+        let tokens = tokenize(&content);
+        let enums = parse_enums(&tokens);
+        let custom_keycodes = custom_keycodes_from_enums(&enums);
+        let tap_dances = parse_table(&tokens, TAP_DANCE_TABLE);
+        let combos = parse_table(&tokens, COMBO_TABLE);
+        let line_index = LineIndex::new(&content);
+
+        if let Ok(path) = uri.to_file_path() {
+            self.key_usage.write().unwrap().set(&path, key_usage(&tokens, &layers));
         }
+
+        let (keycodes, macros, overlay_dirs) = match uri.to_file_path() {
+            Ok(file_path) => {
+                let (overlays, loaded) = self.overlays.get(&file_path, &self.keycodes, &self.macros);
+                // Report broken overlays once, when they are loaded, not on every edit.
+                if loaded {
+                    for error in &overlays.errors {
+                        self.client
+                            .log_message(MessageType::WARNING, format!("Failed to load overlay {}", error))
+                            .await;
+                    }
+                }
+                (overlays.keycodes.clone(), overlays.macros.clone(), overlays.dirs.clone())
+            }
+            Err(_) => (self.keycodes.clone(), self.macros.clone(), Vec::new()),
+        };
+
+        self.documents.documents.insert(uri.clone(), DocumentData {
+            content,
+            layers,
+            custom_keycodes,
+            enums,
+            tap_dances,
+            combos,
+            tokens,
+            line_index,
+            keycodes,
+            macros,
+            overlay_dirs,
+        });
+
+        self.validate(uri).await;
+    }
+
+    /// Semantic tokens for `range`, or the whole document.
+    async fn semantic_tokens(&self, uri: &Url, range: Option<Range>) -> Option<Vec<SemanticToken>> {
+        let (layer_names, declarations) = self.visible_declarations(uri).await;
+        let layer_names: Vec<&str> = layer_names.iter().map(String::as_str).collect();
+        let names = references::declared_symbol_names(&layer_names, &declarations);
+        let doc_entry = self.documents.documents.get(uri)?;
+        let range = range.map(|r| {
+            doc_entry.line_index.offset(&doc_entry.content, r.start)..doc_entry.line_index.offset(&doc_entry.content, r.end)
+        });
+        Some(semantic_tokens::semantic_tokens(&doc_entry, &names, &self.mod_masks, range))
+    }
+
+    /// Layout-aware formatting of the layers in `range`, or of the whole document.
+    fn format_layers(&self, uri: &Url, options: &FormattingOptions, range: Option<Range>) -> Option<Vec<TextEdit>> {
+        let info = find_and_load_info_json(&uri.to_file_path().ok()?)?;
+        let doc_entry = self.documents.documents.get(uri)?;
+        let range = range.map(|r| {
+            doc_entry.line_index.offset(&doc_entry.content, r.start)..doc_entry.line_index.offset(&doc_entry.content, r.end)
+        });
+        Some(format::format_layers(&doc_entry, &info, options, range))
+    }
+
+    /// Shows the layer named by `[uri, layer]` as a grid of legends, and returns the same text.
+    async fn preview_layer(&self, arguments: &[serde_json::Value]) -> Result<Option<serde_json::Value>> {
+        let invalid = || tower_lsp::jsonrpc::Error::invalid_params("Expected [uri, layer] arguments");
+        let (Some(uri), Some(layer)) = (
+            arguments.first().and_then(|a| serde_json::from_value::<Url>(a.clone()).ok()),
+            arguments.get(1).and_then(|a| a.as_str()),
+        ) else {
+            return Err(invalid());
+        };
+        let info = uri.to_file_path().ok().and_then(|path| find_and_load_info_json(&path));
+        let preview = {
+            let Some(doc_entry) = self.documents.documents.get(&uri) else {
+                return Err(invalid());
+            };
+            let Some(layer) = doc_entry.layers.iter().find(|l| l.name == layer) else {
+                return Err(invalid());
+            };
+            let ctx = LegendContext {
+                keycodes: &self.keycodes,
+                macros: &self.macros,
+                mod_masks: &self.mod_masks,
+            };
+            let layout = info.as_ref().and_then(|i| i.layouts.get(&layer.macro_name));
+            code_lens::render_preview(&doc_entry, layer, layout.map(|l| l.layout.as_slice()), &ctx)
+        };
+        self.client.show_message(MessageType::INFO, preview.clone()).await;
+        Ok(Some(serde_json::Value::String(preview)))
+    }
+
+    /// Reveals the `[uri, range]` layout in the editor when the client can show documents,
+    /// and returns it as a location either way.
+    async fn open_layout(&self, arguments: &[serde_json::Value]) -> Result<Option<serde_json::Value>> {
+        let (Some(uri), Some(range)) = (
+            arguments.first().and_then(|a| serde_json::from_value::<Url>(a.clone()).ok()),
+            arguments.get(1).and_then(|a| serde_json::from_value::<Range>(a.clone()).ok()),
+        ) else {
+            return Err(tower_lsp::jsonrpc::Error::invalid_params("Expected [uri, range] arguments"));
+        };
+        let can_show = self
+            .client_capabilities
+            .read()
+            .unwrap()
+            .window
+            .as_ref()
+            .and_then(|w| w.show_document.as_ref())
+            .is_some_and(|s| s.support);
+        if can_show {
+            let _ = self
+                .client
+                .show_document(ShowDocumentParams {
+                    uri: uri.clone(),
+                    external: None,
+                    take_focus: Some(true),
+                    selection: Some(range),
+                })
+                .await;
+        }
+        Ok(serde_json::to_value(Location::new(uri, range)).ok())
+    }
+
+    /// Offers to declare the undeclared, custom-keycode-looking key in `range` along
+    /// with a `process_record_user` handler for it.
+    async fn declare_keycode_action(&self, uri: &Url, range: Range) -> Option<CodeActionOrCommand> {
+        let name = {
+            let doc_entry = self.documents.documents.get(uri)?;
+            let range = doc_entry.line_index.offset(&doc_entry.content, range.start)
+                ..doc_entry.line_index.offset(&doc_entry.content, range.end);
+            declare_keycode::keycode_candidate(&doc_entry.tokens, &doc_entry.layers, &doc_entry.macros, range)?
+        };
+        if self.keycodes.contains_key(&name) || self.macros.contains_key(&name) || self.mod_masks.contains_key(&name) {
+            return None;
+        }
+        // The bundled database misses some of QMK's keycodes: ask the firmware when
+        // there is a checkout, otherwise leave anything named like QMK's alone.
+        let firmware = self.firmware.read().unwrap().clone();
+        let is_qmk = match firmware {
+            Some(firmware) => firmware.index().await.symbols.contains_key(&name),
+            None => declare_keycode::has_qmk_prefix(&name),
+        };
+        if is_qmk {
+            return None;
+        }
+        declare_keycode::code_action(&self.source_files(uri).await, &name)
+    }
+
+    /// Names besides the built-in keycodes an unknown keycode may be a typo of: custom
+    /// keycodes from the keymap and its headers, and its locale keycodes.
+    async fn keycode_candidates(&self, uri: &Url) -> Vec<String> {
+        let mut names: Vec<String> = references::user_symbol_names(&self.source_files(uri).await)
+            .into_iter()
+            .filter(|(_, kind)| *kind == references::UserSymbolKind::CustomKeycode)
+            .map(|(name, _)| name)
+            .collect();
+        names.extend(self.locale_keycodes(uri).await);
+        names
+    }
+
+    /// The locale keycodes of any `keymap_*.h` the document at `uri` includes from the firmware.
+    async fn locale_keycodes(&self, uri: &Url) -> Vec<String> {
+        let locale_headers: Vec<String> = match self.documents.documents.get(uri) {
+            Some(doc_entry) => parse_includes(&doc_entry.content)
+                .into_iter()
+                .map(|include| include.path)
+                .filter(|path| path.rsplit('/').next().is_some_and(|file| file.starts_with("keymap_")))
+                .collect(),
+            None => Vec::new(),
+        };
+        let firmware = self.firmware.read().unwrap().clone();
+        let mut names = Vec::new();
+        if let Some(firmware) = firmware
+            && !locale_headers.is_empty()
+        {
+            let index = firmware.index().await;
+            for header in &locale_headers {
+                names.extend(index.header_symbols(header).map(str::to_string));
+            }
+        }
+        names
+    }
+
+    /// The layer names of the open document at `uri`, and its declarations followed by
+    /// those of every header it includes, transitively, like `source_files`. Open files
+    /// are parsed from their buffers and the rest taken from the workspace index once it
+    /// is built; until then, or when an include is not indexed, they are read from disk.
+    async fn visible_declarations(&self, uri: &Url) -> (Vec<String>, Vec<Arc<Declarations>>) {
+        let Some(layer_names) = self
+            .documents
+            .documents
+            .get(uri)
+            .map(|doc| doc.layers.iter().map(|l| l.name.clone()).collect())
+        else {
+            return (Vec::new(), Vec::new());
+        };
+        let Ok(path) = uri.to_file_path() else {
+            let declarations = self.open_declarations(uri).map(|(_, d)| d).into_iter().collect();
+            return (layer_names, declarations);
+        };
+
+        // Don't wait for the workspace walk: the first requests after startup shouldn't block on it.
+        let indexed = self
+            .workspace_index
+            .get()
+            .and_then(|index| self.indexed_declarations(&index.read().unwrap(), path));
+        let declarations = match indexed {
+            Some(declarations) => declarations,
+            None => self
+                .source_files(uri)
+                .await
+                .iter()
+                .map(|file| Arc::new(Declarations::parse(&file.content, &file.tokens)))
+                .collect(),
+        };
+        (layer_names, declarations)
+    }
+
+    /// The declarations of `path` and the headers it includes, from open buffers and
+    /// `index`. `None` if an include doesn't resolve to an indexed file.
+    fn indexed_declarations(&self, index: &WorkspaceIndex, path: PathBuf) -> Option<Vec<Arc<Declarations>>> {
+        let mut paths = vec![path];
+        let mut declarations = Vec::new();
+        let mut next = 0;
+        while let Some(path) = paths.get(next).cloned() {
+            next += 1;
+            let open = Url::from_file_path(&path).ok().and_then(|uri| self.open_declarations(&uri));
+            let (includes, file) = match open {
+                Some(open) => open,
+                None => match index.sources.get(&path) {
+                    Some(source) => (source.includes.clone(), source.declarations.clone()),
+                    None => continue,
+                },
+            };
+            declarations.push(file);
+            for include in &includes {
+                let header = index.resolve_include(&path, include)?;
+                let source = header.with_extension("c");
+                let source = index.sources.contains_key(&source).then_some(source);
+                for path in std::iter::once(header).chain(source) {
+                    if !paths.contains(&path) {
+                        paths.push(path);
+                    }
+                }
+            }
+        }
+        Some(declarations)
+    }
+
+    /// Includes and declarations of the open document at `uri`.
+    fn open_declarations(&self, uri: &Url) -> Option<(Vec<String>, Arc<Declarations>)> {
+        let doc = self.documents.documents.get(uri)?;
+        let includes = parse_includes(&doc.content).into_iter().map(|i| i.path).collect();
+        let declarations = Declarations {
+            enums: doc.enums.clone(),
+            defines: parse_defines(&doc.content),
+        };
+        Some((includes, Arc::new(declarations)))
+    }
+
+    /// The open document at `uri` followed by every header it includes, transitively,
+    /// and the userspace source next to each header (`users/me/me.c` for `me.h`).
+    /// Open files are read from their buffers, others from disk on a blocking thread.
+    async fn source_files(&self, uri: &Url) -> Vec<SourceFile> {
+        let roots = self.workspace_roots.read().unwrap().clone();
+        let open = self.open_buffers();
+        let uri = uri.clone();
+        tokio::task::spawn_blocking(move || read_source_files(&uri, &open, &roots))
+            .await
+            .unwrap_or_default()
+    }
+
+    /// The contents of every open document.
+    fn open_buffers(&self) -> HashMap<Url, String> {
+        self.documents
+            .documents
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.content.clone()))
+            .collect()
     }
 
+    /// Rewrites every deprecated keycode in the workspace's keymaps to its preferred replacement.
+    async fn migrate_workspace(&self) -> Result<Option<serde_json::Value>> {
+        let roots = self.workspace_roots.read().unwrap().clone();
+        let open = self.open_buffers();
+        // Walk the workspace and read the keymaps that aren't open on a blocking thread.
+        let sources = tokio::task::spawn_blocking(move || {
+            roots
+                .iter()
+                .flat_map(|root| keymap_sources(root))
+                .filter_map(|path| {
+                    let uri = Url::from_file_path(&path).ok()?;
+                    let content = match open.get(&uri) {
+                        Some(content) => content.clone(),
+                        None => std::fs::read_to_string(&path).ok()?,
+                    };
+                    Some((path, uri, content))
+                })
+                .collect::<Vec<_>>()
+        })
+        .await
+        .unwrap_or_default();
 
+        let mut changes = HashMap::new();
+        let mut count = 0;
+        for (path, uri, content) in sources {
+            let (overlays, _) = self.overlays.get(&path, &self.keycodes, &self.macros);
+            let edits = migrate::migrate_source(&content, &overlays.keycodes, &overlays.macros);
+            if !edits.is_empty() {
+                count += edits.len();
+                changes.insert(uri, edits);
+            }
+        }
+
+        if changes.is_empty() {
+            self.client
+                .show_message(MessageType::INFO, "No deprecated keycodes found.")
+                .await;
+            return Ok(None);
+        }
+
+        let files = changes.len();
+        let edit = WorkspaceEdit {
+            changes: Some(changes),
+            ..Default::default()
+        };
+        match self.client.apply_edit(edit).await {
+            Ok(response) if response.applied => {
+                self.client
+                    .show_message(MessageType::INFO, format!("Migrated {} deprecated keycodes in {} files.", count, files))
+                    .await;
+            }
+            Ok(response) => {
+                let reason = response.failure_reason.unwrap_or_else(|| "edit rejected".to_string());
+                self.client
+                    .show_message(MessageType::ERROR, format!("Keycode migration failed: {}", reason))
+                    .await;
+            }
+            Err(e) => return Err(e),
+        }
+        Ok(Some(serde_json::json!({ "files": files, "edits": count })))
+    }
 
     async fn validate(&self, uri: Url) {
+        let locale = self.locale_keycodes(&uri).await;
         let doc_entry = match self.documents.documents.get(&uri) {
             Some(d) => d,
             None => return,
@@ -36,94 +542,148 @@ impl Backend {
         let content = &doc_entry.content;
         let custom_keycodes = &doc_entry.custom_keycodes;
         let layers = &doc_entry.layers;
+        let keycodes = &doc_entry.keycodes;
+
+        let line_index = &doc_entry.line_index;
+        let get_position = |byte_idx: usize| -> Position { line_index.position(content, byte_idx) };
 
         let mut diagnostics = Vec::new();
-        let re = Regex::new(r"\bKC_[a-zA-Z0-9_]+\b").unwrap();
-
-        let get_position = |byte_idx: usize| -> Position {
-             let mut line = 0;
-             let mut character = 0;
-             
-             for (idx, c) in content.char_indices() {
-                 if idx == byte_idx {
-                     break;
-                 }
-                 if c == '\n' {
-                     line += 1;
-                     character = 0;
-                 } else {
-                     character += c.len_utf16() as u32;
-                 }
-             }
-             Position::new(line, character)
-        };
 
         // 1. Keycode validation
-        for cap in re.captures_iter(content) {
-            if let Some(m) = cap.get(0) {
-                let text = m.as_str();
-                let start_pos = get_position(m.start());
-                let end_pos = get_position(m.end());
-
-                let is_valid = self.keycodes.contains_key(text) || 
-                               custom_keycodes.iter().any(|ck| ck.name == text);
-
-                if !is_valid {
-                    diagnostics.push(Diagnostic {
-                        range: Range::new(start_pos, end_pos),
-                        severity: Some(DiagnosticSeverity::ERROR),
-                        code: Some(NumberOrString::String("unknown_keycode".to_string())),
-                        source: Some("qmk-lsp".to_string()),
-                        message: format!("Unknown QMK keycode: '{}'", text),
-                        ..Default::default()
-                    });
-                }
-            }
+        let is_known = |name: &str| keycodes.contains_key(name) || custom_keycodes.iter().any(|ck| ck.name == name);
+        for token in typos::unknown_keycodes(&doc_entry.tokens, layers, &locale, is_known) {
+            diagnostics.push(Diagnostic {
+                range: line_index.range(content, token.span.clone()),
+                severity: Some(DiagnosticSeverity::ERROR),
+                code: Some(NumberOrString::String(typos::UNKNOWN_KEYCODE.to_string())),
+                source: Some("qmk-lsp".to_string()),
+                message: format!("Unknown QMK keycode: '{}'", token.text),
+                ..Default::default()
+            });
         }
-        
+
+        // Deprecated and removed keycodes
+        for deprecated in migrate::find_deprecated(content, &doc_entry.tokens, layers, &doc_entry.macros, keycodes) {
+            diagnostics.push(Diagnostic {
+                range: line_index.range(content, deprecated.span),
+                severity: Some(DiagnosticSeverity::WARNING),
+                code: Some(NumberOrString::String(migrate::DEPRECATED_KEYCODE.to_string())),
+                source: Some("qmk-lsp".to_string()),
+                message: migrate::deprecated_message(&deprecated.keycode.name, deprecated.replacements),
+                tags: Some(vec![DiagnosticTag::DEPRECATED]),
+                ..Default::default()
+            });
+        }
+
         // 2. Layer size validation against info.json
-        if let Ok(file_path) = uri.to_file_path() {
-            if let Some(info) = find_and_load_info_json(&file_path) {
-                for layer in layers {
-                    if let Some(layout_map) = info.layouts.get(&layer.macro_name) {
-                        let expected_count = layout_map.layout.len();
-                        if layer.key_count != expected_count {
-                            let start_pos = get_position(layer.span.start);
-                            let end_pos = get_position(layer.span.end);
-                            
-                            diagnostics.push(Diagnostic {
-                                range: Range::new(start_pos, end_pos),
-                                severity: Some(DiagnosticSeverity::ERROR), // Or WARNING? User can decide, defaulting to ERROR as it breaks keymap.
-                                code: Some(NumberOrString::String("layer_mismatch".to_string())),
-                                source: Some("qmk-lsp".to_string()),
-                                message: format!("Layout mismatch: '{}' expects {} keys, found {}.", layer.macro_name, expected_count, layer.key_count),
-                                ..Default::default()
-                            });
-                        }
+        if let Ok(file_path) = uri.to_file_path()
+            && let Some(info) = find_and_load_info_json(&file_path)
+        {
+            for layer in layers {
+                if let Some(layout_map) = info.layouts.get(&layer.macro_name) {
+                    let expected_count = layout_map.layout.len();
+                    if layer.key_count != expected_count {
+                        let start_pos = get_position(layer.span.start);
+                        let end_pos = get_position(layer.span.end);
+                        let extra: Vec<DiagnosticRelatedInformation> = layout_fixes::extra_keys(layer, expected_count)
+                            .iter()
+                            .map(|key| DiagnosticRelatedInformation {
+                                location: Location::new(uri.clone(), line_index.range(content, key.clone())),
+                                message: format!("Extra key '{}'", &content[key.clone()]),
+                            })
+                            .collect();
+
+                        diagnostics.push(Diagnostic {
+                            range: Range::new(start_pos, end_pos),
+                            severity: Some(DiagnosticSeverity::ERROR), // Or WARNING? User can decide, defaulting to ERROR as it breaks keymap.
+                            code: Some(NumberOrString::String(layout_fixes::LAYER_MISMATCH.to_string())),
+                            source: Some("qmk-lsp".to_string()),
+                            message: format!("Layout mismatch: '{}' expects {} keys, found {}.", layer.macro_name, expected_count, layer.key_count),
+                            related_information: (!extra.is_empty()).then_some(extra),
+                            ..Default::default()
+                        });
                     }
                 }
             }
         }
 
+        // Release the document before awaiting so edits are never blocked on the client.
+        drop(doc_entry);
         self.client.publish_diagnostics(uri, diagnostics, None).await;
     }
 }
 
 #[tower_lsp::async_trait]
 impl LanguageServer for Backend {
-    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
         eprintln!("Received initialize request");
+        *self.client_capabilities.write().unwrap() = params.capabilities;
+
+        let mut roots: Vec<PathBuf> = params
+            .workspace_folders
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|folder| folder.uri.to_file_path().ok())
+            .collect();
+        #[allow(deprecated)]
+        if roots.is_empty()
+            && let Some(root) = params.root_uri.and_then(|uri| uri.to_file_path().ok())
+        {
+            roots.push(root);
+        }
+        *self.workspace_roots.write().unwrap() = roots;
+        self.configure(Settings::from_value(params.initialization_options.as_ref())).await;
         Ok(InitializeResult {
             capabilities: ServerCapabilities {
                 text_document_sync: Some(TextDocumentSyncCapability::Kind(
                     TextDocumentSyncKind::FULL,
                 )),
                 completion_provider: Some(CompletionOptions {
+                    trigger_characters: Some(vec!["(".to_string(), ",".to_string()]),
+                    resolve_provider: Some(true),
+                    ..Default::default()
+                }),
+                signature_help_provider: Some(SignatureHelpOptions {
                     trigger_characters: Some(vec!["(".to_string(), ",".to_string()]),
                     ..Default::default()
                 }),
                 hover_provider: Some(HoverProviderCapability::Simple(true)),
+                references_provider: Some(OneOf::Left(true)),
+                rename_provider: Some(OneOf::Right(RenameOptions {
+                    prepare_provider: Some(true),
+                    work_done_progress_options: Default::default(),
+                })),
+                document_symbol_provider: Some(OneOf::Left(true)),
+                document_highlight_provider: Some(OneOf::Left(true)),
+                folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+                selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+                inlay_hint_provider: Some(OneOf::Left(true)),
+                semantic_tokens_provider: Some(SemanticTokensServerCapabilities::SemanticTokensOptions(SemanticTokensOptions {
+                    legend: semantic_tokens::legend(),
+                    full: Some(SemanticTokensFullOptions::Bool(true)),
+                    range: Some(true),
+                    ..Default::default()
+                })),
+                document_formatting_provider: Some(OneOf::Left(true)),
+                document_range_formatting_provider: Some(OneOf::Left(true)),
+                workspace_symbol_provider: Some(OneOf::Left(true)),
                 definition_provider: Some(OneOf::Left(true)),
+                implementation_provider: Some(ImplementationProviderCapability::Simple(true)),
+                code_action_provider: Some(CodeActionProviderCapability::Options(CodeActionOptions {
+                    code_action_kinds: Some(vec![CodeActionKind::QUICKFIX]),
+                    ..Default::default()
+                })),
+                code_lens_provider: Some(CodeLensOptions {
+                    resolve_provider: Some(false),
+                }),
+                execute_command_provider: Some(ExecuteCommandOptions {
+                    commands: vec![
+                        migrate::MIGRATE_COMMAND.to_string(),
+                        code_lens::PREVIEW_LAYER_COMMAND.to_string(),
+                        code_lens::OPEN_LAYOUT_COMMAND.to_string(),
+                    ],
+                    ..Default::default()
+                }),
                 ..Default::default()
             },
             ..Default::default()
@@ -134,6 +694,42 @@ impl LanguageServer for Backend {
         self.client
             .log_message(MessageType::INFO, "QMK LSP initialized!")
             .await;
+
+        let index = self.workspace_index.clone();
+        let roots = self.workspace_roots.read().unwrap().clone();
+        let key_usage = self.key_usage.clone();
+        tokio::spawn(async move {
+            index.get_or_init(|| build_index(roots, key_usage)).await;
+        });
+
+        let can_watch = self
+            .client_capabilities
+            .read()
+            .unwrap()
+            .workspace
+            .as_ref()
+            .and_then(|w| w.did_change_watched_files)
+            .and_then(|w| w.dynamic_registration)
+            .unwrap_or(false);
+        if can_watch {
+            let options = DidChangeWatchedFilesRegistrationOptions {
+                watchers: ["**/.qmk-lsp/*.json", "**/*.{c,h}", "**/{info,keyboard}.json"]
+                    .into_iter()
+                    .map(|glob| FileSystemWatcher {
+                        glob_pattern: GlobPattern::String(glob.to_string()),
+                        kind: None,
+                    })
+                    .collect(),
+            };
+            let registration = Registration {
+                id: "qmk-lsp-watched-files".to_string(),
+                method: "workspace/didChangeWatchedFiles".to_string(),
+                register_options: serde_json::to_value(options).ok(),
+            };
+            if let Err(e) = self.client.register_capability(vec![registration]).await {
+                eprintln!("Failed to register file watchers: {}", e);
+            }
+        }
         eprintln!("Initialized notification processed");
     }
 
@@ -148,57 +744,18 @@ impl LanguageServer for Backend {
     ) -> Result<Option<GotoDefinitionResponse>> {
         let uri = params.text_document_position_params.text_document.uri;
         let position = params.text_document_position_params.position;
-        
-        let doc_entry = match self.documents.documents.get(&uri) {
-            Some(d) => d,
-            None => return Ok(None),
-        };
-        let content = &doc_entry.content;
 
-        // Get word under cursor (Quick reuse of hover logic logic... should abstract this maybe)
-        let lines: Vec<&str> = content.lines().collect();
-        if position.line as usize >= lines.len() {
-             return Ok(None);
-        }
-        let line = lines[position.line as usize];
-        let mut byte_offset = 0;
-        let mut utf16_count = 0;
-        for (i, c) in line.char_indices() {
-             if utf16_count == position.character as usize {
-                 byte_offset = i;
-                 break;
-             }
-             if utf16_count > position.character as usize {
-                 byte_offset = i;
-                 break;
-             }
-             utf16_count += c.len_utf16();
-        }
-        if utf16_count == position.character as usize && byte_offset == 0 && !line.is_empty() {
-             byte_offset = line.len(); 
-        }
-        
-        // Scan word
-        let mut start_byte = byte_offset;
-        let mut end_byte = byte_offset;
-        while start_byte > 0 {
-            let (prev_char_idx, prev_char) = line[..start_byte].char_indices().next_back().unwrap();
-             if !prev_char.is_alphanumeric() && prev_char != '_' {
-                break;
-            }
-            start_byte = prev_char_idx;
-        }
-        while end_byte < line.len() {
-             let (_, cur_char) = line[end_byte..].char_indices().next().unwrap();
-             if !cur_char.is_alphanumeric() && cur_char != '_' {
-                 break;
-             }
-             end_byte += cur_char.len_utf8();
-        }
-        if start_byte >= end_byte {
+        let files = self.source_files(&uri).await;
+        let Some(doc) = files.first() else {
             return Ok(None);
+        };
+        let offset = doc.line_index.offset(&doc.content, position);
+        if let Some(location) = definition::definition(&files, offset) {
+            return Ok(Some(GotoDefinitionResponse::Scalar(location)));
         }
-        let word = &line[start_byte..end_byte];
+        let Some(word) = token_at(&doc.tokens, offset).map(|i| doc.tokens[i].text.as_str()) else {
+            return Ok(None);
+        };
 
         // Layouts in parsing info.json
         if let Ok(file_path) = uri.to_file_path() {
@@ -221,93 +778,377 @@ impl LanguageServer for Backend {
             }
         }
 
+        let firmware = self.firmware.read().unwrap().clone();
+        if let Some(firmware) = firmware
+            && let Some(location) = firmware.index().await.location(word)
+        {
+            return Ok(Some(GotoDefinitionResponse::Scalar(location)));
+        }
+
         Ok(None)
     }
 
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        let uri = params.text_document.uri;
+        let diagnostics = &params.context.diagnostics;
+        let unknown = NumberOrString::String(typos::UNKNOWN_KEYCODE.to_string());
+        let known_names = if diagnostics.iter().any(|d| d.code.as_ref() == Some(&unknown)) {
+            self.keycode_candidates(&uri).await
+        } else {
+            Vec::new()
+        };
+        let mismatch = NumberOrString::String(layout_fixes::LAYER_MISMATCH.to_string());
+        let info = if diagnostics.iter().any(|d| d.code.as_ref() == Some(&mismatch)) {
+            uri.to_file_path().ok().and_then(|path| find_and_load_info_json(&path))
+        } else {
+            None
+        };
+
+        let declare = self.declare_keycode_action(&uri, params.range).await;
+
+        let doc_entry = match self.documents.documents.get(&uri) {
+            Some(d) => d,
+            None => return Ok(None),
+        };
+
+        let mut actions = Vec::new();
+        actions.extend(migrate::code_actions(&uri, &doc_entry, diagnostics));
+        actions.extend(typos::code_actions(&uri, &doc_entry, diagnostics, &known_names));
+        if let Some(info) = &info {
+            actions.extend(layout_fixes::code_actions(&uri, &doc_entry, diagnostics, info));
+        }
+        actions.extend(declare);
+
+        if actions.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(actions))
+    }
+
+    async fn execute_command(&self, params: ExecuteCommandParams) -> Result<Option<serde_json::Value>> {
+        match params.command.as_str() {
+            migrate::MIGRATE_COMMAND => self.migrate_workspace().await,
+            code_lens::PREVIEW_LAYER_COMMAND => self.preview_layer(&params.arguments).await,
+            code_lens::OPEN_LAYOUT_COMMAND => self.open_layout(&params.arguments).await,
+            _ => Err(tower_lsp::jsonrpc::Error::invalid_params(format!("Unknown command: {}", params.command))),
+        }
+    }
 
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
         eprintln!("Opened document: {}", params.text_document.uri);
-        let content = params.text_document.text;
-        let layers = parse_layers(&content);
-        let custom_keycodes = parse_custom_keycodes(&content);
-        
-        let uri = params.text_document.uri.clone();
-        self.documents.documents.insert(uri.clone(), DocumentData {
-            content,
-            layers,
-            custom_keycodes,
-        });
-
-        self.validate(uri).await;
+        self.update_document(params.text_document.uri, params.text_document.text).await;
     }
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
         if let Some(change) = params.content_changes.into_iter().next() {
-            let content = change.text;
-            let layers = parse_layers(&content);
-            let custom_keycodes = parse_custom_keycodes(&content);
-
-            let uri = params.text_document.uri.clone();
-            self.documents.documents.insert(uri.clone(), DocumentData {
-                content,
-                layers,
-                custom_keycodes,
-            });
-
-            self.validate(uri).await;
+            self.update_document(params.text_document.uri, change.text).await;
         }
     }
 
-    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
-        let uri = params.text_document_position.text_document.uri;
-        let mut items = Vec::new();
-
-        // 1. KC_* keycodes from JSON
-        for (name, kc) in &self.keycodes {
-            items.push(CompletionItem {
-                label: name.clone(),
-                kind: Some(CompletionItemKind::CONSTANT),
-                detail: Some(kc.description.clone()),
-                documentation: Some(Documentation::String(kc.doc.clone())),
-                ..Default::default()
-            });
+    async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
+        self.configure(Settings::from_value(Some(&params.settings))).await;
+
+        // The hint style may have changed.
+        let can_refresh = self
+            .client_capabilities
+            .read()
+            .unwrap()
+            .workspace
+            .as_ref()
+            .and_then(|w| w.inlay_hint.as_ref())
+            .and_then(|i| i.refresh_support)
+            .unwrap_or(false);
+        if can_refresh && let Err(e) = self.client.inlay_hint_refresh().await {
+            eprintln!("Failed to refresh inlay hints: {}", e);
         }
+    }
 
-        // 2. Custom Keycodes and Layers from parsed document
-        if let Some(doc_data) = self.documents.documents.get(&uri) {
-             for layer in &doc_data.layers {
-                items.push(CompletionItem {
-                    label: layer.name.clone(),
-                    kind: Some(CompletionItemKind::ENUM_MEMBER),
-                    detail: Some("Layer".to_string()),
-                    ..Default::default()
-                });
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        let paths: Vec<PathBuf> = params
+            .changes
+            .iter()
+            .filter_map(|change| change.uri.to_file_path().ok())
+            .collect();
+
+        // Read the changed files off the async runtime, then update the index and the
+        // key usage without holding their locks during I/O.
+        let indexed: Vec<PathBuf> = paths
+            .iter()
+            .filter(|p| is_keymap_source(p) || is_info_json(p))
+            .cloned()
+            .collect();
+        let contents = tokio::task::spawn_blocking(move || {
+            indexed
+                .into_iter()
+                .map(|path| {
+                    let content = std::fs::read_to_string(&path).ok();
+                    (path, content)
+                })
+                .collect::<Vec<_>>()
+        })
+        .await
+        .unwrap_or_default();
+
+        // Only keep an index that has been built up to date; a pending build reads the files anyway.
+        if let Some(index) = self.workspace_index.get() {
+            let mut index = index.write().unwrap();
+            for (path, content) in &contents {
+                index.set_file(path, content.as_deref());
             }
-             for custom in &doc_data.custom_keycodes {
-                items.push(CompletionItem {
-                    label: custom.name.clone(),
-                    kind: Some(CompletionItemKind::ENUM_MEMBER),
-                    detail: Some("Custom Keycode".to_string()),
-                    ..Default::default()
-                });
+        }
+        // Open buffers are counted as edited; only files on disk are recounted.
+        for (path, content) in &contents {
+            let open = Url::from_file_path(path).is_ok_and(|uri| self.documents.documents.contains_key(&uri));
+            if open || !is_keymap_source(path) {
+                continue;
             }
+            let usage = content
+                .as_deref()
+                .map(|content| key_usage(&tokenize(content), &parse_layers(content)))
+                .unwrap_or_default();
+            self.key_usage.write().unwrap().set(path, usage);
         }
 
-        // 3. Macros from JSON
-        for (name, mac) in &self.macros {
-            items.push(CompletionItem {
-                label: name.clone(),
-                kind: Some(CompletionItemKind::FUNCTION),
-                detail: Some(mac.detail.clone()),
-                insert_text: Some(format!("{}(", name)), 
-                insert_text_format: Some(InsertTextFormat::PLAIN_TEXT), 
-                documentation: Some(Documentation::String(mac.doc.clone())),
-                ..Default::default()
-            });
+        let overlay_changed = paths.iter().any(|path| is_overlay_file(path));
+        if !overlay_changed {
+            return;
         }
 
+        // Overlays are merged per document, so every open document has to be re-resolved.
+        self.overlays.clear();
+        self.overlay_completion_caches.clear();
+        let open: Vec<(Url, String)> = self
+            .documents
+            .documents
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.content.clone()))
+            .collect();
+        for (uri, content) in open {
+            self.update_document(uri, content).await;
+        }
+    }
+
+    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        let uri = params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+
+        let doc_entry = match self.documents.documents.get(&uri) {
+            Some(d) => d,
+            None => return Ok(None),
+        };
+        let offset = doc_entry.line_index.offset(&doc_entry.content, position);
+        let snippets = self.supports_snippets();
+
+        // Never wait for the workspace index while typing; keymaps it hasn't read yet just don't count.
+        let key_usage = self.key_usage.read().unwrap();
+        let usage = &key_usage.totals;
+
+        let list = if doc_entry.overlay_dirs.is_empty() {
+            completion::completion_list(&uri, &doc_entry, &self.completion_cache, usage, offset, snippets)
+        } else {
+            let cache = self
+                .overlay_completion_caches
+                .entry(doc_entry.overlay_dirs.clone())
+                .or_insert_with(|| Arc::new(CompletionCache::new(&doc_entry.keycodes, &doc_entry.macros, &self.mod_masks)))
+                .clone();
+            completion::completion_list(&uri, &doc_entry, &cache, usage, offset, snippets)
+        };
+
+        Ok(Some(CompletionResponse::List(list)))
+    }
+
+    async fn completion_resolve(&self, item: CompletionItem) -> Result<CompletionItem> {
+        let uri = item
+            .data
+            .as_ref()
+            .and_then(|d| d.get("uri"))
+            .and_then(|u| serde_json::from_value::<Url>(u.clone()).ok());
+        let doc_entry = uri.and_then(|uri| self.documents.documents.get(&uri));
+        Ok(completion::resolve(item, doc_entry.as_deref(), &self.keycodes, &self.macros, &self.mod_masks))
+    }
+
+    async fn signature_help(&self, params: SignatureHelpParams) -> Result<Option<SignatureHelp>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+
+        let doc_entry = match self.documents.documents.get(&uri) {
+            Some(d) => d,
+            None => return Ok(None),
+        };
+        let offset = doc_entry.line_index.offset(&doc_entry.content, position);
+        Ok(signature_help::signature_help(&doc_entry, offset))
+    }
+
+    async fn goto_implementation(
+        &self,
+        params: GotoImplementationParams,
+    ) -> Result<Option<GotoImplementationResponse>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+
+        let files = self.source_files(&uri).await;
+        let Some(doc) = files.first() else {
+            return Ok(None);
+        };
+        let offset = doc.line_index.offset(&doc.content, position);
+        Ok(definition::implementation(&files, offset).map(GotoImplementationResponse::Scalar))
+    }
+
+    async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+        let uri = params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+
+        let files = self.source_files(&uri).await;
+        let Some(doc) = files.first() else {
+            return Ok(None);
+        };
+        let offset = doc.line_index.offset(&doc.content, position);
+        let Some(token) = token_at(&doc.tokens, offset).map(|i| &doc.tokens[i]).filter(|t| t.is_ident()) else {
+            return Ok(None);
+        };
+        let Some(symbol) = references::find_symbol(&token.text, &files) else {
+            return Ok(None);
+        };
+        Ok(Some(references::find_references(&symbol, &files, params.context.include_declaration)))
+    }
+
+    async fn prepare_rename(&self, params: TextDocumentPositionParams) -> Result<Option<PrepareRenameResponse>> {
+        let files = self.source_files(&params.text_document.uri).await;
+        let Some(doc) = files.first() else {
+            return Ok(None);
+        };
+        let offset = doc.line_index.offset(&doc.content, params.position);
+        Ok(rename::prepare_rename(&files, offset).map(|(_, range)| PrepareRenameResponse::Range(range)))
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let uri = params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+
+        let files = self.source_files(&uri).await;
+        let Some(doc) = files.first() else {
+            return Ok(None);
+        };
+        let offset = doc.line_index.offset(&doc.content, position);
+        let Some((symbol, _)) = rename::prepare_rename(&files, offset) else {
+            return Ok(None);
+        };
+        let (keycodes, macros) = match self.documents.documents.get(&uri) {
+            Some(d) => (d.keycodes.clone(), d.macros.clone()),
+            None => return Ok(None),
+        };
+        rename::rename_edit(&symbol, &files, &params.new_name, &keycodes, &macros, &self.mod_masks)
+            .map(Some)
+            .map_err(tower_lsp::jsonrpc::Error::invalid_params)
+    }
+
+    async fn semantic_tokens_full(&self, params: SemanticTokensParams) -> Result<Option<SemanticTokensResult>> {
+        let tokens = self.semantic_tokens(&params.text_document.uri, None).await;
+        Ok(tokens.map(|data| SemanticTokensResult::Tokens(SemanticTokens { result_id: None, data })))
+    }
+
+    async fn semantic_tokens_range(&self, params: SemanticTokensRangeParams) -> Result<Option<SemanticTokensRangeResult>> {
+        let tokens = self.semantic_tokens(&params.text_document.uri, Some(params.range)).await;
+        Ok(tokens.map(|data| SemanticTokensRangeResult::Tokens(SemanticTokens { result_id: None, data })))
+    }
 
-        Ok(Some(CompletionResponse::Array(items)))
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        Ok(self.format_layers(&params.text_document.uri, &params.options, None))
+    }
+
+    async fn range_formatting(&self, params: DocumentRangeFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        Ok(self.format_layers(&params.text_document.uri, &params.options, Some(params.range)))
+    }
+
+    async fn inlay_hint(&self, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
+        let uri = params.text_document.uri;
+        let info = uri.to_file_path().ok().and_then(|path| find_and_load_info_json(&path));
+        let style = self.settings.read().unwrap().inlay_hints;
+        let (layer_names, declarations) = self.visible_declarations(&uri).await;
+        let layer_names: Vec<&str> = layer_names.iter().map(String::as_str).collect();
+        let indices = references::declared_layer_indices(&layer_names, &declarations);
+
+        let doc_entry = match self.documents.documents.get(&uri) {
+            Some(d) => d,
+            None => return Ok(None),
+        };
+        let range = doc_entry.line_index.offset(&doc_entry.content, params.range.start)
+            ..doc_entry.line_index.offset(&doc_entry.content, params.range.end);
+        let mut hints = inlay_hints::inlay_hints(&doc_entry, info.as_ref(), style, range.clone());
+        hints.extend(inlay_hints::layer_index_hints(&doc_entry, &indices, range));
+        Ok(Some(hints))
+    }
+
+    async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
+        let uri = params.text_document.uri;
+        let info_path = uri.to_file_path().ok().and_then(|path| find_info_json_path(&path));
+        let info = info_path.and_then(|path| {
+            let content = std::fs::read_to_string(&path).ok()?;
+            let info: KeyboardInfo = serde_json::from_str(&content).ok()?;
+            Some((Url::from_file_path(&path).ok()?, content, info))
+        });
+
+        let doc_entry = match self.documents.documents.get(&uri) {
+            Some(d) => d,
+            None => return Ok(None),
+        };
+        let info = info.as_ref().map(|(uri, content, info)| InfoJson { uri, content, info });
+        Ok(Some(code_lens::code_lenses(&uri, &doc_entry, info.as_ref())))
+    }
+
+    async fn document_highlight(&self, params: DocumentHighlightParams) -> Result<Option<Vec<DocumentHighlight>>> {
+        let position = params.text_document_position_params.position;
+        let doc_entry = match self.documents.documents.get(&params.text_document_position_params.text_document.uri) {
+            Some(d) => d,
+            None => return Ok(None),
+        };
+        let offset = doc_entry.line_index.offset(&doc_entry.content, position);
+        Ok(document_highlight::document_highlights(&doc_entry, offset))
+    }
+
+    async fn folding_range(&self, params: FoldingRangeParams) -> Result<Option<Vec<FoldingRange>>> {
+        let doc_entry = match self.documents.documents.get(&params.text_document.uri) {
+            Some(d) => d,
+            None => return Ok(None),
+        };
+        Ok(Some(folding_ranges::folding_ranges(&doc_entry)))
+    }
+
+    async fn selection_range(&self, params: SelectionRangeParams) -> Result<Option<Vec<SelectionRange>>> {
+        let doc_entry = match self.documents.documents.get(&params.text_document.uri) {
+            Some(d) => d,
+            None => return Ok(None),
+        };
+        Ok(Some(
+            params
+                .positions
+                .into_iter()
+                .map(|position| selection_ranges::selection_range(&doc_entry, position))
+                .collect(),
+        ))
+    }
+
+    async fn document_symbol(&self, params: DocumentSymbolParams) -> Result<Option<DocumentSymbolResponse>> {
+        let doc_entry = match self.documents.documents.get(&params.text_document.uri) {
+            Some(d) => d,
+            None => return Ok(None),
+        };
+        Ok(Some(DocumentSymbolResponse::Nested(symbols::document_symbols(&doc_entry))))
+    }
+
+    async fn symbol(&self, params: WorkspaceSymbolParams) -> Result<Option<Vec<SymbolInformation>>> {
+        let index = self.workspace_index().await.read().unwrap();
+        // Open documents may have unsaved edits, so index their buffers instead of the files.
+        let open: Vec<_> = self
+            .documents
+            .documents
+            .iter()
+            .filter_map(|entry| {
+                let path = entry.key().to_file_path().ok()?;
+                Some((entry.key().clone(), index.file_symbols(&path, &entry.content)?))
+            })
+            .collect();
+        Ok(Some(workspace_symbols::workspace_symbols(&index, &open, &params.query)))
     }
 
     async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
@@ -378,37 +1219,59 @@ impl LanguageServer for Backend {
         let mut hover_text = String::new();
 
         // Check KC_* keycodes
-        if let Some(kc) = self.keycodes.get(word) {
+        if let Some(kc) = doc_entry.keycodes.get(word) {
+            let legend = match kc.legend.as_deref() {
+                Some(legend) if !legend.is_empty() => format!("\n\n**Legend:** {}", legend),
+                _ => String::new(),
+            };
             hover_text = format!(
-                "### {}\n\n**Category:** {}\n\n{}\n\n---\n\n#### Documentation\n{}",
-                kc.name, kc.category, kc.description, kc.doc
+                "### {}\n\n**Category:** {}{}\n\n{}\n\n---\n\n#### Documentation\n{}",
+                kc.name, kc.category, legend, kc.description, kc.doc
             );
+            if let Some(deprecation) = &kc.deprecated {
+                hover_text.push_str(&format!(
+                    "\n\n**Deprecated:** {}",
+                    migrate::deprecated_message(&kc.name, &deprecation.replacements)
+                ));
+            }
         } 
         // Check Macros (from JSON)
-        else if let Some(mac) = self.macros.get(word) {
+        else if let Some(mac) = doc_entry.macros.get(word) {
             hover_text = format!(
                 "### {}\n\n**{}**\n\n{}\n\n---\n\n#### Documentation\n{}",
                 mac.name, mac.detail, mac.description, mac.doc
             );
+            if !mac.params.is_empty() {
+                hover_text.push_str(&format!("\n\n#### Parameters\n`{}`\n", mac.signature()));
+                for param in &mac.params {
+                    hover_text.push_str(&format!("\n- `{}`: {}", param.name, param.kind.describe()));
+                }
+            }
+        }
+        // Check MOD_* masks
+        else if let Some(mask) = self.mod_masks.get(word) {
+            hover_text = format!(
+                "### {}\n\n**Modifier Mask**\n\n{}\n\n---\n\n#### Documentation\n{}",
+                mask.name, mask.description, mask.doc
+            );
         }
         // Check Custom Keycodes
         else if let Some(custom) = doc_entry.custom_keycodes.iter().find(|c| c.name == word) {
              hover_text = format!("### {} (Custom Keycode)\n\nDefined in this file.", custom.name);
         }
         // Check Layouts from info.json
-        else if let Ok(file_path) = uri.to_file_path() {
-            if let Some(info) = find_and_load_info_json(&file_path) {
-                if let Some(layout) = info.layouts.get(word) {
-                    let kb_name = info.keyboard_name.as_deref().unwrap_or("Unknown Keyboard");
-                    let maintainer = info.maintainer.as_deref().unwrap_or("Unknown");
-                    let key_count = layout.layout.len();
-                    
-                    hover_text = format!(
-                        "### {}\n\n**Keyboard:** {}\n\n**Keys:** {}\n\n**Maintainer:** {}\n\n---\n\n#### Layout Info\nThis layout is defined in `info.json` or `keyboard.json`.",
-                        word, kb_name, key_count, maintainer
-                    );
-                }
-            }
+        else if let Ok(file_path) = uri.to_file_path()
+            && let Some(info) = find_and_load_info_json(&file_path)
+            && let Some(layout) = info.layouts.get(word)
+        {
+            let kb_name = info.keyboard_name.as_deref().unwrap_or("Unknown Keyboard");
+            let maintainer = info.maintainer.as_deref().unwrap_or("Unknown");
+            let key_count = layout.layout.len();
+
+            hover_text = format!(
+                "### {}\n\n**Keyboard:** {}\n\n**Keys:** {}\n\n**Maintainer:** {}\n\n---\n\n#### Layout Info\nThis layout is defined in `info.json` or `keyboard.json`.",
+                word, kb_name, key_count, maintainer
+            );
         }
         
         if hover_text.is_empty() {
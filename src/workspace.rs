@@ -0,0 +1,374 @@
+use crate::document::LineIndex;
+use crate::features::references::Declarations;
+use crate::parser::includes::parse_includes;
+use crate::parser::custom::parse_custom_keycodes;
+use crate::parser::layers::{parse_layers, Layer};
+use crate::parser::tokens::{tokenize, Token};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tower_lsp::lsp_types::Range;
+
+/// Directories that never contain user keymaps and are expensive to walk.
+const SKIPPED_DIRS: &[&str] = &["lib", "build", "target", "node_modules"];
+
+/// Returns true for C sources and headers that belong to a keymap or userspace:
+/// anything under a `keymaps/` or `users/` directory, or a file named `keymap.*`.
+pub fn is_keymap_source(path: &Path) -> bool {
+    let is_c = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e == "c" || e == "h");
+    if !is_c {
+        return false;
+    }
+
+    let is_keymap_file = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .is_some_and(|s| s.starts_with("keymap"));
+    is_keymap_file
+        || path
+            .components()
+            .any(|c| c.as_os_str() == "keymaps" || c.as_os_str() == "users")
+}
+
+/// Recursively collects every keymap source below `root`.
+pub fn keymap_sources(root: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    walk(root, &mut |path| {
+        if is_keymap_source(path) {
+            files.push(path.to_path_buf());
+        }
+    });
+    files.sort();
+    files
+}
+
+/// Calls `visit` for every file below `dir`, skipping hidden and build directories.
+pub fn walk(dir: &Path, visit: &mut dyn FnMut(&Path)) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with('.') || SKIPPED_DIRS.contains(&name.as_ref()) {
+                continue;
+            }
+            walk(&path, visit);
+        } else if file_type.is_file() {
+            visit(&path);
+        }
+    }
+}
+
+/// Resolves a quoted include the way QMK's build does for user code: next to
+/// the including file, then up through the keyboard directories, then in every
+/// `users/<name>` directory of the workspace.
+pub fn resolve_include(from: &Path, include: &str, roots: &[PathBuf]) -> Option<PathBuf> {
+    let dir = from.parent()?;
+    let within_roots = |d: &Path| roots.iter().any(|root| d.starts_with(root));
+    let ancestors = dir.ancestors().take_while(|d| *d == dir || within_roots(d));
+    for ancestor in ancestors {
+        let candidate = ancestor.join(include);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+    }
+
+    for root in roots {
+        let Ok(users) = fs::read_dir(root.join("users")) else {
+            continue;
+        };
+        let mut users: Vec<PathBuf> = users.flatten().map(|e| e.path()).collect();
+        users.sort();
+        if let Some(candidate) = users.iter().map(|u| u.join(include)).find(|c| c.is_file()) {
+            return Some(candidate);
+        }
+    }
+    None
+}
+
+/// Returns true for the keyboard definitions that declare layouts.
+pub fn is_info_json(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|n| n == "info.json" || n == "keyboard.json")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexedKind {
+    Layer,
+    CustomKeycode,
+    /// A layout macro used by a keymap's layers.
+    LayoutMacro,
+    /// A layout declared in an info.json or keyboard.json.
+    Layout,
+}
+
+#[derive(Debug, Clone)]
+pub struct IndexedSymbol {
+    pub name: String,
+    pub kind: IndexedKind,
+    pub range: Range,
+    /// The keymap directory or keyboard the symbol belongs to.
+    pub container: String,
+}
+
+/// What a keymap source includes and declares, so features following includes
+/// don't have to read the headers again.
+#[derive(Debug, Default)]
+pub struct IndexedSource {
+    pub includes: Vec<String>,
+    pub declarations: Arc<Declarations>,
+}
+
+impl IndexedSource {
+    pub fn parse(content: &str) -> Self {
+        IndexedSource {
+            includes: parse_includes(content).into_iter().map(|i| i.path).collect(),
+            declarations: Arc::new(Declarations::parse(content, &tokenize(content))),
+        }
+    }
+}
+
+/// Symbols of every keymap source and keyboard definition below the workspace roots.
+#[derive(Debug, Default)]
+pub struct WorkspaceIndex {
+    pub roots: Vec<PathBuf>,
+    pub files: HashMap<PathBuf, Vec<IndexedSymbol>>,
+    pub sources: HashMap<PathBuf, IndexedSource>,
+    /// Key usage of the keymap sources as read by `build`.
+    pub usage: KeyUsage,
+}
+
+impl WorkspaceIndex {
+    pub fn build(roots: &[PathBuf]) -> Self {
+        let mut index = WorkspaceIndex {
+            roots: roots.to_vec(),
+            ..Default::default()
+        };
+        for root in roots {
+            walk(root, &mut |path| {
+                if !is_keymap_source(path) && !is_info_json(path) {
+                    return;
+                }
+                let content = fs::read_to_string(path).ok();
+                index.set_file(path, content.as_deref());
+                if let Some(content) = content.filter(|_| is_keymap_source(path)) {
+                    index.usage.set(path, key_usage(&tokenize(&content), &parse_layers(&content)));
+                }
+            });
+        }
+        index
+    }
+
+    /// Indexes `path` given its `content`, or drops it if the file is gone.
+    pub fn set_file(&mut self, path: &Path, content: Option<&str>) {
+        match content.and_then(|content| self.file_symbols(path, content)) {
+            Some(symbols) => {
+                self.files.insert(path.to_path_buf(), symbols);
+            }
+            None => {
+                self.files.remove(path);
+            }
+        }
+        match content.filter(|_| is_keymap_source(path)) {
+            Some(content) => {
+                self.sources.insert(path.to_path_buf(), IndexedSource::parse(content));
+            }
+            None => {
+                self.sources.remove(path);
+            }
+        }
+    }
+
+    /// Resolves an include like `resolve_include`, but among the indexed sources
+    /// instead of on disk.
+    pub fn resolve_include(&self, from: &Path, include: &str) -> Option<PathBuf> {
+        let dir = from.parent()?;
+        let within_roots = |d: &Path| self.roots.iter().any(|root| d.starts_with(root));
+        let ancestors = dir.ancestors().take_while(|d| *d == dir || within_roots(d));
+        for ancestor in ancestors {
+            let candidate = ancestor.join(include);
+            if self.sources.contains_key(&candidate) {
+                return Some(candidate);
+            }
+        }
+
+        for root in &self.roots {
+            let users = root.join("users");
+            let found = self
+                .sources
+                .keys()
+                .filter(|path| {
+                    let user = path
+                        .strip_prefix(&users)
+                        .ok()
+                        .and_then(|rest| rest.components().next());
+                    user.is_some_and(|user| **path == users.join(user).join(include))
+                })
+                .min();
+            if let Some(found) = found {
+                return Some(found.clone());
+            }
+        }
+        None
+    }
+
+    /// Symbols of `path` given its `content`, or None if it is neither a keymap source nor a keyboard definition.
+    pub fn file_symbols(&self, path: &Path, content: &str) -> Option<Vec<IndexedSymbol>> {
+        if is_keymap_source(path) {
+            Some(source_symbols(content, &self.container(path)))
+        } else if is_info_json(path) {
+            Some(layout_symbols(content, &self.container(path)))
+        } else {
+            None
+        }
+    }
+
+    /// The directory holding `path`, relative to the workspace root it lives in.
+    fn container(&self, path: &Path) -> String {
+        let dir = path.parent().unwrap_or(path);
+        let relative = self
+            .roots
+            .iter()
+            .find_map(|root| dir.strip_prefix(root).ok())
+            .filter(|r| !r.as_os_str().is_empty())
+            .unwrap_or(dir);
+        relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+/// How often each identifier is used in keys, per file and in total. Files are
+/// replaced one at a time so the totals never have to be recounted.
+#[derive(Debug, Default)]
+pub struct KeyUsage {
+    files: HashMap<PathBuf, HashMap<String, usize>>,
+    pub totals: HashMap<String, usize>,
+}
+
+impl KeyUsage {
+    pub fn contains(&self, path: &Path) -> bool {
+        self.files.contains_key(path)
+    }
+
+    /// Replaces the counts of `path`, adjusting the totals by the difference.
+    pub fn set(&mut self, path: &Path, usage: HashMap<String, usize>) {
+        if let Some(old) = self.files.remove(path) {
+            for (name, count) in old {
+                if let Some(total) = self.totals.get_mut(&name) {
+                    *total -= count;
+                    if *total == 0 {
+                        self.totals.remove(&name);
+                    }
+                }
+            }
+        }
+        if usage.is_empty() {
+            return;
+        }
+        for (name, count) in &usage {
+            *self.totals.entry(name.clone()).or_insert(0) += count;
+        }
+        self.files.insert(path.to_path_buf(), usage);
+    }
+
+    /// Takes the counts of every file in `other` not counted here yet.
+    pub fn merge_missing(&mut self, other: KeyUsage) {
+        for (path, usage) in other.files {
+            if !self.contains(&path) {
+                self.set(&path, usage);
+            }
+        }
+    }
+}
+
+/// How often each identifier appears in the keys of `layers`.
+pub fn key_usage(tokens: &[Token], layers: &[Layer]) -> HashMap<String, usize> {
+    let mut usage = HashMap::new();
+    for key in layers.iter().flat_map(|l| &l.keys) {
+        let start = tokens.partition_point(|t| t.span.start < key.start);
+        for token in tokens[start..].iter().take_while(|t| t.span.end <= key.end) {
+            if token.is_ident() {
+                *usage.entry(token.text.clone()).or_insert(0) += 1;
+            }
+        }
+    }
+    usage
+}
+
+/// Layers, custom keycodes and the layout macros used in a keymap source.
+pub fn source_symbols(content: &str, container: &str) -> Vec<IndexedSymbol> {
+    let line_index = LineIndex::new(content);
+    let symbol = |name: &str, kind, span: std::ops::Range<usize>| IndexedSymbol {
+        name: name.to_string(),
+        kind,
+        range: line_index.range(content, span),
+        container: container.to_string(),
+    };
+
+    let mut symbols = Vec::new();
+    let layers = parse_layers(content);
+    for layer in &layers {
+        symbols.push(symbol(&layer.name, IndexedKind::Layer, layer.name_span.clone()));
+    }
+    for custom in parse_custom_keycodes(content) {
+        symbols.push(symbol(&custom.name, IndexedKind::CustomKeycode, custom.span));
+    }
+    let mut seen = Vec::new();
+    for layer in &layers {
+        if seen.contains(&&layer.macro_name) {
+            continue;
+        }
+        seen.push(&layer.macro_name);
+        let Some(start) = content[layer.span.clone()].find(&layer.macro_name) else {
+            continue;
+        };
+        let start = layer.span.start + start;
+        symbols.push(symbol(&layer.macro_name, IndexedKind::LayoutMacro, start..start + layer.macro_name.len()));
+    }
+    symbols
+}
+
+/// Layouts declared in an info.json or keyboard.json.
+pub fn layout_symbols(content: &str, container: &str) -> Vec<IndexedSymbol> {
+    let Ok(json) = serde_json::from_str::<serde_json::Value>(content) else {
+        return Vec::new();
+    };
+    let Some(layouts) = json.get("layouts").and_then(|l| l.as_object()) else {
+        return Vec::new();
+    };
+    let container = json
+        .get("keyboard_name")
+        .and_then(|n| n.as_str())
+        .unwrap_or(container);
+
+    let line_index = LineIndex::new(content);
+    let layouts_start = content.find("\"layouts\"").unwrap_or(0);
+    layouts
+        .keys()
+        .map(|name| {
+            // Point at the key itself; fall back to the `layouts` object if the text search misses.
+            let start = content[layouts_start..]
+                .find(&format!("\"{}\"", name))
+                .map_or(layouts_start, |i| layouts_start + i + 1);
+            IndexedSymbol {
+                name: name.clone(),
+                kind: IndexedKind::Layout,
+                range: line_index.range(content, start..start + name.len()),
+                container: container.to_string(),
+            }
+        })
+        .collect()
+}
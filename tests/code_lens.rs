@@ -0,0 +1,112 @@
+mod common;
+
+use std::fs;
+use tower_lsp::lsp_types::*;
+
+const INFO_JSON: &str = r#"{
+  "keyboard_name": "test",
+  "layouts": {
+    "LAYOUT_2x2": {
+      "layout": [
+        { "matrix": [0, 0], "x": 0, "y": 0 },
+        { "matrix": [0, 1], "x": 1, "y": 0 },
+        { "matrix": [1, 0], "x": 0, "y": 1 },
+        { "matrix": [1, 1], "x": 1, "y": 1 }
+      ]
+    }
+  }
+}"#;
+
+const KEYMAP: &str = "enum layers { _BASE, _NAV };
+const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {
+    [_BASE] = LAYOUT_2x2(
+        KC_ESC, KC_Q,
+        MO(_NAV), LT(_NAV, KC_A)
+    ),
+    [_NAV] = LAYOUT_2x2(
+        _______, KC_TRNS,
+        _______
+    )
+};
+";
+
+fn titles(lenses: &[CodeLens]) -> Vec<(u32, &str, &str)> {
+    lenses
+        .iter()
+        .map(|l| {
+            let command = l.command.as_ref().unwrap();
+            (l.range.start.line, command.title.as_str(), command.command.as_str())
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_code_lens_layer_summaries() {
+    let dir = common::temp_dir("test_qmk_lsp_code_lens");
+    fs::write(dir.join("info.json"), INFO_JSON).unwrap();
+    let uri = Url::from_file_path(dir.join("keymap.c")).unwrap();
+    let info_uri = Url::from_file_path(dir.join("info.json")).unwrap();
+    let mut service = common::start(InitializeParams::default()).await;
+    common::open(&mut service, &uri, KEYMAP).await;
+
+    let params = CodeLensParams {
+        text_document: TextDocumentIdentifier { uri: uri.clone() },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    let lenses: Vec<CodeLens> = common::request(&mut service, "textDocument/codeLens", params).await;
+    assert_eq!(
+        titles(&lenses),
+        vec![
+            (2, "4/4 keys", ""),
+            (2, "Preview", "qmk.previewLayer"),
+            (2, "Open LAYOUT_2x2 in info.json", "qmk.openLayout"),
+            (6, "3/4 keys · 3 transparent · reached via MO from _BASE, LT from _BASE", ""),
+            (6, "Preview", "qmk.previewLayer"),
+            (6, "Open LAYOUT_2x2 in info.json", "qmk.openLayout"),
+        ]
+    );
+    assert_eq!(lenses[0].range, Range::new(Position::new(2, 5), Position::new(2, 10)));
+
+    // The open-layout command points at the layout's key in info.json.
+    let arguments = lenses[2].command.as_ref().unwrap().arguments.clone().unwrap();
+    let location: Location = common::request(
+        &mut service,
+        "workspace/executeCommand",
+        ExecuteCommandParams {
+            command: "qmk.openLayout".to_string(),
+            arguments,
+            work_done_progress_params: Default::default(),
+        },
+    )
+    .await;
+    assert_eq!(location.uri, info_uri);
+    assert_eq!(location.range.start.line, 3);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn test_code_lens_preview_layer() {
+    let dir = common::temp_dir("test_qmk_lsp_code_lens_preview");
+    fs::write(dir.join("info.json"), INFO_JSON).unwrap();
+    let uri = Url::from_file_path(dir.join("keymap.c")).unwrap();
+    let mut service = common::start(InitializeParams::default()).await;
+    common::open(&mut service, &uri, KEYMAP).await;
+
+    let preview: String = common::request(
+        &mut service,
+        "workspace/executeCommand",
+        ExecuteCommandParams {
+            command: "qmk.previewLayer".to_string(),
+            arguments: vec![serde_json::json!(uri), serde_json::json!("_BASE")],
+            work_done_progress_params: Default::default(),
+        },
+    )
+    .await;
+    let lines: Vec<&str> = preview.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], "_BASE (LAYOUT_2x2)");
+    assert!(lines[1].starts_with("Esc"), "{}", preview);
+    assert!(lines[2].contains("Nav"), "{}", preview);
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,66 @@
+#![allow(dead_code)]
+
+use qmk_lsp::server::Backend;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tower_lsp::lsp_types::*;
+use tower_lsp::{jsonrpc, LspService};
+use tower_service::Service;
+
+/// Creates a fresh, uniquely named directory under the system temp dir.
+pub fn temp_dir(prefix: &str) -> PathBuf {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let dir = std::env::temp_dir().join(format!("{}_{}", prefix, now));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Starts a server and runs the initialize handshake.
+pub async fn start(params: InitializeParams) -> LspService<Backend> {
+    let (mut service, _) = LspService::new(Backend::new);
+    let init_req = jsonrpc::Request::build("initialize")
+        .params(serde_json::to_value(params).unwrap())
+        .id(0)
+        .finish();
+    let _ = service.call(init_req).await.unwrap();
+
+    let initialized_req = jsonrpc::Request::build("initialized")
+        .params(serde_json::to_value(InitializedParams {}).unwrap())
+        .finish();
+    let _ = service.call(initialized_req).await.unwrap();
+    service
+}
+
+pub async fn open(service: &mut LspService<Backend>, uri: &Url, text: &str) {
+    let params = DidOpenTextDocumentParams {
+        text_document: TextDocumentItem {
+            uri: uri.clone(),
+            language_id: "c".to_string(),
+            version: 1,
+            text: text.to_string(),
+        },
+    };
+    let req = jsonrpc::Request::build("textDocument/didOpen")
+        .params(serde_json::to_value(params).unwrap())
+        .finish();
+    let _ = service.call(req).await.unwrap();
+}
+
+pub async fn request<P: Serialize, R: DeserializeOwned>(service: &mut LspService<Backend>, method: &'static str, params: P) -> R {
+    let req = jsonrpc::Request::build(method)
+        .params(serde_json::to_value(params).unwrap())
+        .id(1)
+        .finish();
+    let response = service.call(req).await.unwrap().unwrap();
+    let (_, result) = response.into_parts();
+    serde_json::from_value(result.unwrap()).unwrap()
+}
+
+pub fn position_params(uri: &Url, line: u32, character: u32) -> TextDocumentPositionParams {
+    TextDocumentPositionParams {
+        text_document: TextDocumentIdentifier { uri: uri.clone() },
+        position: Position::new(line, character),
+    }
+}
@@ -0,0 +1,175 @@
+mod common;
+
+use qmk_lsp::features::completion::macro_snippet;
+use qmk_lsp::parser::macros::load_macros;
+use tower_lsp::lsp_types::*;
+
+const KEYMAP: &str = "\
+enum custom_keycodes { MY_KEY = SAFE_RANGE };
+tap_dance_action_t tap_dance_actions[] = {
+    [TD_ESC_CAPS] = ACTION_TAP_DANCE_DOUBLE(KC_ESC, KC_CAPS),
+};
+const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {
+    [_BASE] = LAYOUT(KC_A, MO(), LT(_NAV, KC_S), OSM(), TD(), , M),
+    [_NAV] = LAYOUT(KC_B, KC_SPC, KC_SPC, KC_E, KC_F, KC_G, KC_H)
+};";
+
+async fn complete_at(needle: &str) -> CompletionList {
+    complete_with(InitializeParams::default(), needle).await
+}
+
+async fn complete_with(init: InitializeParams, needle: &str) -> CompletionList {
+    complete_in(init, KEYMAP, needle).await
+}
+
+async fn complete_in(init: InitializeParams, text: &str, needle: &str) -> CompletionList {
+    let dir = common::temp_dir("test_qmk_lsp_completion");
+    let uri = Url::from_file_path(dir.join("keymap.c")).unwrap();
+    let mut service = common::start(init).await;
+    common::open(&mut service, &uri, text).await;
+
+    let offset = text.find(needle).unwrap() + needle.len();
+    let line = text[..offset].matches('\n').count() as u32;
+    let character = (offset - text[..offset].rfind('\n').map_or(0, |i| i + 1)) as u32;
+
+    let params = CompletionParams {
+        text_document_position: common::position_params(&uri, line, character),
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+        context: None,
+    };
+    let response: Option<CompletionResponse> = common::request(&mut service, "textDocument/completion", params).await;
+    let _ = std::fs::remove_dir_all(&dir);
+
+    match response.unwrap() {
+        CompletionResponse::List(list) => list,
+        CompletionResponse::Array(_) => panic!("Expected a completion list"),
+    }
+}
+
+fn labels(list: &CompletionList) -> Vec<&str> {
+    list.items.iter().map(|i| i.label.as_str()).collect()
+}
+
+#[tokio::test]
+async fn test_completion_layer_param() {
+    let list = complete_at("MO(").await;
+    // _NAV is used twice in the document, _BASE once.
+    assert_eq!(labels(&list), vec!["_NAV", "_BASE"]);
+    assert!(!list.is_incomplete);
+}
+
+#[tokio::test]
+async fn test_completion_basic_keycode_param() {
+    let list = complete_at("LT(_NAV, KC_S").await;
+    let labels = labels(&list);
+    // Usage ranks KC_SPC first, then the rest alphabetically.
+    assert_eq!(labels[0], "KC_SPC");
+    assert!(labels.contains(&"KC_SCLN"));
+    assert!(labels.iter().all(|l| l.starts_with("KC_S")));
+    assert!(!labels.contains(&"KC_SLCK"));
+}
+
+#[tokio::test]
+async fn test_completion_mod_mask_and_tap_dance() {
+    let list = complete_at("OSM(").await;
+    assert!(labels(&list).contains(&"MOD_LSFT"));
+    assert!(labels(&list).iter().all(|l| l.starts_with("MOD_")));
+
+    let list = complete_at("TD(").await;
+    assert_eq!(labels(&list), vec!["TD_ESC_CAPS"]);
+}
+
+#[tokio::test]
+async fn test_completion_layout_top_level() {
+    let list = complete_at("TD(), ").await;
+    assert!(list.is_incomplete);
+    assert_eq!(list.items.len(), qmk_lsp::features::completion::MAX_COMPLETIONS);
+    assert_eq!(&labels(&list)[..3], ["_NAV", "_BASE", "MY_KEY"]);
+
+    let list = complete_at(", M").await;
+    let labels = labels(&list);
+    for expected in ["MY_KEY", "MO", "MT", "KC_MUTE"] {
+        assert!(labels.contains(&expected), "missing {}", expected);
+    }
+    assert!(list.items.iter().all(|i| i.documentation.is_none()));
+    assert!(list.items.windows(2).all(|w| w[0].sort_text < w[1].sort_text));
+}
+
+#[tokio::test]
+async fn test_completion_outside_layers() {
+    let text = "\
+enum layers { _BASE, _NAV, _ADJUST };
+const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {
+    [_BASE] = LAYOUT(MO()),
+};
+bool process_record_user(uint16_t keycode, keyrecord_t *record) {
+    switch (keycode) {
+        case KC_ESC:
+            layer_move(_A);
+            foo(x, );
+    }
+}";
+    // Layers come from the enum, including ones without a keymap entry yet.
+    let list = complete_in(InitializeParams::default(), text, "MO(").await;
+    assert_eq!(labels(&list), vec!["_ADJUST", "_BASE", "_NAV"]);
+
+    let list = complete_in(InitializeParams::default(), text, "layer_move(_A").await;
+    assert_eq!(labels(&list), vec!["_ADJUST"]);
+
+    let list = complete_in(InitializeParams::default(), text, "case KC_ES").await;
+    let labels = labels(&list);
+    assert!(labels.contains(&"KC_ESC"));
+    assert!(labels.iter().all(|l| l.starts_with("KC_ES")));
+
+    let list = complete_in(InitializeParams::default(), text, "foo(x, ").await;
+    assert!(list.items.is_empty());
+}
+
+#[tokio::test]
+async fn test_completion_resolve() {
+    let list = complete_at(", M").await;
+    let item = list.items.into_iter().find(|i| i.label == "MO").unwrap();
+
+    let mut service = common::start(InitializeParams::default()).await;
+    let resolved: CompletionItem = common::request(&mut service, "completionItem/resolve", item).await;
+    assert!(matches!(resolved.documentation, Some(Documentation::String(doc)) if doc.starts_with("MO(layer)")));
+}
+
+#[test]
+fn test_macro_snippets() {
+    let macros = load_macros();
+    assert_eq!(macro_snippet(&macros["LT"], &[]), "LT(${1:layer}, ${2:KC_})");
+    assert_eq!(macro_snippet(&macros["LT"], &["_BASE", "_NAV"]), "LT(${1|_BASE,_NAV|}, ${2:KC_})");
+    assert_eq!(macro_snippet(&macros["MT"], &["_BASE"]), "MT(${1:MOD_LCTL}, ${2:kc})");
+    assert_eq!(macro_snippet(&macros["OSM"], &[]), "OSM(${1:MOD_LSFT})");
+}
+
+#[tokio::test]
+async fn test_completion_snippet_support() {
+    let list = complete_at(", M").await;
+    let mo = list.items.iter().find(|i| i.label == "MO").unwrap();
+    assert_eq!(mo.insert_text.as_deref(), Some("MO("));
+    assert_eq!(mo.insert_text_format, Some(InsertTextFormat::PLAIN_TEXT));
+
+    let init = InitializeParams {
+        capabilities: ClientCapabilities {
+            text_document: Some(TextDocumentClientCapabilities {
+                completion: Some(CompletionClientCapabilities {
+                    completion_item: Some(CompletionItemCapability {
+                        snippet_support: Some(true),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let list = complete_with(init, ", M").await;
+    let mo = list.items.iter().find(|i| i.label == "MO").unwrap();
+    assert_eq!(mo.insert_text.as_deref(), Some("MO(${1|_BASE,_NAV|})"));
+    assert_eq!(mo.insert_text_format, Some(InsertTextFormat::SNIPPET));
+}
@@ -0,0 +1,174 @@
+mod common;
+
+use tower_lsp::lsp_types::*;
+
+async fn declare(text: &str, line: u32, character: u32) -> Option<(String, String)> {
+    let uri = Url::parse("file:///test_declare/keymap.c").unwrap();
+    let mut service = common::start(InitializeParams::default()).await;
+    common::open(&mut service, &uri, text).await;
+
+    let position = Position::new(line, character);
+    let params = CodeActionParams {
+        text_document: TextDocumentIdentifier { uri: uri.clone() },
+        range: Range::new(position, position),
+        context: CodeActionContext::default(),
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    let actions: Option<CodeActionResponse> = common::request(&mut service, "textDocument/codeAction", params).await;
+    let action = actions?.into_iter().find_map(|a| match a {
+        CodeActionOrCommand::CodeAction(a) if a.title.starts_with("Declare") => Some(a),
+        _ => None,
+    })?;
+    let mut edits = action.edit.unwrap().changes.unwrap().remove(&uri).unwrap();
+    edits.sort_by_key(|e| std::cmp::Reverse(e.range.start));
+    Some((action.title, apply(text, &edits)))
+}
+
+/// Applies insertions, last first.
+fn apply(text: &str, edits: &[TextEdit]) -> String {
+    let mut lines: Vec<String> = text.split('\n').map(str::to_string).collect();
+    for edit in edits {
+        let line = &mut lines[edit.range.start.line as usize];
+        line.insert_str(edit.range.start.character as usize, &edit.new_text);
+    }
+    lines.join("\n")
+}
+
+#[tokio::test]
+async fn test_declare_keycode_creates_enum_and_handler() {
+    let keymap = "#include QMK_KEYBOARD_H
+
+const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {
+    [0] = LAYOUT(KC_A, MY_MACRO)
+};
+";
+    let (title, text) = declare(keymap, 3, 26).await.unwrap();
+    assert_eq!(title, "Declare custom keycode 'MY_MACRO'");
+    assert_eq!(
+        text,
+        "#include QMK_KEYBOARD_H
+
+enum custom_keycodes {
+    MY_MACRO = SAFE_RANGE,
+};
+
+const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {
+    [0] = LAYOUT(KC_A, MY_MACRO)
+};
+
+bool process_record_user(uint16_t keycode, keyrecord_t *record) {
+    switch (keycode) {
+        case MY_MACRO:
+            if (record->event.pressed) {
+                // MY_MACRO pressed
+            }
+            return false;
+    }
+    return true;
+}
+"
+    );
+}
+
+#[tokio::test]
+async fn test_declare_keycode_without_keymaps_goes_below_includes() {
+    let keymap = "#include QMK_KEYBOARD_H
+#include \"macros.h\"
+
+static const uint16_t PROGMEM base[] = {
+    [0] = LAYOUT(KC_A, MY_MACRO)
+};
+";
+    let (_, text) = declare(keymap, 4, 26).await.unwrap();
+    assert!(text.starts_with(
+        "#include QMK_KEYBOARD_H
+#include \"macros.h\"
+
+enum custom_keycodes {
+    MY_MACRO = SAFE_RANGE,
+};
+
+static const uint16_t PROGMEM base[] = {"
+    ));
+}
+
+#[tokio::test]
+async fn test_declare_keycode_extends_enum_and_handler() {
+    let keymap = "enum custom_keycodes {
+    FIRST = SAFE_RANGE,
+};
+
+const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {
+    [0] = LAYOUT(FIRST, LCTL(SECOND))
+};
+
+bool process_record_user_taps(uint16_t keycode) {
+    switch (keycode) {
+    }
+    return true;
+}
+
+bool process_record_user(uint16_t keycode, keyrecord_t *record) {
+    switch (get_highest_layer(layer_state)) {
+    }
+    switch (keycode) {
+    case FIRST:
+        return false;
+    }
+    return true;
+}
+";
+    let (_, text) = declare(keymap, 5, 31).await.unwrap();
+    assert_eq!(
+        text,
+        "enum custom_keycodes {
+    FIRST = SAFE_RANGE,
+    SECOND,
+};
+
+const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {
+    [0] = LAYOUT(FIRST, LCTL(SECOND))
+};
+
+bool process_record_user_taps(uint16_t keycode) {
+    switch (keycode) {
+    }
+    return true;
+}
+
+bool process_record_user(uint16_t keycode, keyrecord_t *record) {
+    switch (get_highest_layer(layer_state)) {
+    }
+    switch (keycode) {
+    case SECOND:
+        if (record->event.pressed) {
+            // SECOND pressed
+        }
+        return false;
+    case FIRST:
+        return false;
+    }
+    return true;
+}
+"
+    );
+}
+
+#[tokio::test]
+async fn test_declare_keycode_skips_known_names() {
+    let keymap = "enum custom_keycodes { FIRST = SAFE_RANGE };
+const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {
+    [0] = LAYOUT(KC_ESC, FIRST, my_key, MO(0), LT(1, SECOND), QK_REP, MS_BTN1, DE_Z)
+};
+";
+    assert!(declare(keymap, 2, 19).await.is_none());
+    assert!(declare(keymap, 2, 25).await.is_none());
+    assert!(declare(keymap, 2, 32).await.is_none());
+    assert!(declare(keymap, 2, 39).await.is_none());
+    // A basic keycode argument, and QMK keycodes missing from the bundled database.
+    assert!(declare(keymap, 2, 55).await.is_none());
+    assert!(declare(keymap, 2, 64).await.is_none());
+    assert!(declare(keymap, 2, 72).await.is_none());
+    assert!(declare(keymap, 2, 80).await.is_none());
+}
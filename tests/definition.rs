@@ -1,3 +1,5 @@
+mod common;
+
 use std::fs::{self, File};
 use std::io::Write;
 use std::time::{SystemTime, UNIX_EPOCH};
@@ -28,7 +30,7 @@ async fn test_goto_definition() {
     let keymap_uri = Url::from_file_path(&keymap_path).unwrap();
 
     // Mock client
-    let (mut service, _) = LspService::new(|client| Backend::new(client));
+    let (mut service, _) = LspService::new(Backend::new);
     
     // Initialize
     let init_params = InitializeParams::default();
@@ -41,7 +43,7 @@ async fn test_goto_definition() {
     // Initialized notification
     let initialized_params = InitializedParams {};
     let initialized_req = jsonrpc::Request::build("initialized")
-        .params(serde_json::to_value(&initialized_params).unwrap())
+        .params(serde_json::to_value(initialized_params).unwrap())
         .finish();
     let _ = service.call(initialized_req).await.unwrap();
 
@@ -97,3 +99,89 @@ async fn test_goto_definition() {
     // Cleanup
     let _ = fs::remove_dir_all(&dir_path);
 }
+
+const USER_H: &str = r#"#pragma once
+enum userspace_keycodes { VIM_SAVE = SAFE_RANGE };
+#define HOME_A LGUI_T(KC_A) // home row mod
+"#;
+
+const USER_C: &str = r#"#include "me.h"
+bool process_record_keymap(uint16_t keycode, keyrecord_t *record);
+bool process_record_user(uint16_t keycode, keyrecord_t *record) {
+    switch (keycode) {
+    case VIM_SAVE:
+        return false;
+    }
+    return process_record_keymap(keycode, record);
+}
+"#;
+
+const KEYMAP: &str = r#"#include "me.h"
+enum layers { _BASE, _NAV };
+const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {
+    [_BASE] = LAYOUT(MO(_NAV), VIM_SAVE, HOME_A),
+    [_NAV] = LAYOUT(KC_LEFT, KC_RGHT, KC_NO)
+};
+"#;
+
+async fn locate(method: &'static str, line: u32, character: u32) -> Option<(String, Range)> {
+    let dir = common::temp_dir("test_qmk_lsp_def_user");
+    let keymap_dir = dir.join("keyboards/kb/keymaps/me");
+    fs::create_dir_all(&keymap_dir).unwrap();
+    fs::create_dir_all(dir.join("users/me")).unwrap();
+    fs::write(dir.join("users/me/me.h"), USER_H).unwrap();
+    fs::write(dir.join("users/me/me.c"), USER_C).unwrap();
+
+    let uri = Url::from_file_path(keymap_dir.join("keymap.c")).unwrap();
+    let mut service = common::start(InitializeParams {
+        root_uri: Some(Url::from_file_path(&dir).unwrap()),
+        ..Default::default()
+    })
+    .await;
+    common::open(&mut service, &uri, KEYMAP).await;
+
+    let params = GotoDefinitionParams {
+        text_document_position_params: common::position_params(&uri, line, character),
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    let response: Option<GotoDefinitionResponse> = common::request(&mut service, method, params).await;
+    let _ = fs::remove_dir_all(&dir);
+    match response? {
+        GotoDefinitionResponse::Scalar(location) => {
+            let file = location.uri.path().rsplit('/').next().unwrap().to_string();
+            Some((file, location.range))
+        }
+        _ => panic!("Expected a single location"),
+    }
+}
+
+fn range(line: u32, start: u32, end: u32) -> Range {
+    Range::new(Position::new(line, start), Position::new(line, end))
+}
+
+#[tokio::test]
+async fn test_goto_definition_layer() {
+    // `_NAV` in MO(_NAV) goes to the layer block.
+    assert_eq!(locate("textDocument/definition", 3, 25).await, Some(("keymap.c".to_string(), range(4, 5, 9))));
+    // From the block, on to the enum member.
+    assert_eq!(locate("textDocument/definition", 4, 6).await, Some(("keymap.c".to_string(), range(1, 21, 25))));
+}
+
+#[tokio::test]
+async fn test_goto_definition_userspace() {
+    // `VIM_SAVE` goes to the enum member in the included userspace header.
+    assert_eq!(locate("textDocument/definition", 3, 31).await, Some(("me.h".to_string(), range(1, 26, 34))));
+    // `HOME_A` goes to its #define.
+    assert_eq!(locate("textDocument/definition", 3, 41).await, Some(("me.h".to_string(), range(2, 8, 14))));
+    // Builtin keycodes have no definition.
+    assert_eq!(locate("textDocument/definition", 4, 22).await, None);
+}
+
+#[tokio::test]
+async fn test_goto_implementation() {
+    // `VIM_SAVE` goes to its case in the userspace process_record_user.
+    assert_eq!(locate("textDocument/implementation", 3, 31).await, Some(("me.c".to_string(), range(4, 4, 18))));
+    // Layers have no implementation.
+    assert_eq!(locate("textDocument/implementation", 3, 25).await, None);
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use tower_lsp::lsp_types::*;
+
+const KEYMAP: &str = "enum layers { _BASE, _NAV };
+enum custom_keycodes { MACRO_1 = SAFE_RANGE };
+// KC_ESC and _NAV in a comment.
+const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {
+    [_BASE] = LAYOUT(KC_ESC, MO(_NAV), MACRO_1),
+    [_NAV] = LAYOUT(KC_ESC, _______, _______)
+};
+const char *name = \"_NAV\";
+";
+
+async fn highlights(line: u32, character: u32) -> Vec<(Range, DocumentHighlightKind)> {
+    let uri = Url::parse("file:///test_highlight/keymap.c").unwrap();
+    let mut service = common::start(InitializeParams::default()).await;
+    common::open(&mut service, &uri, KEYMAP).await;
+    let params = DocumentHighlightParams {
+        text_document_position_params: common::position_params(&uri, line, character),
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    let highlights: Option<Vec<DocumentHighlight>> = common::request(&mut service, "textDocument/documentHighlight", params).await;
+    highlights
+        .unwrap_or_default()
+        .into_iter()
+        .map(|h| (h.range, h.kind.unwrap()))
+        .collect()
+}
+
+fn range(line: u32, start: u32, end: u32) -> Range {
+    Range::new(Position::new(line, start), Position::new(line, end))
+}
+
+#[tokio::test]
+async fn test_document_highlight_layer() {
+    let found = highlights(4, 33).await;
+    assert_eq!(
+        found,
+        vec![
+            (range(0, 21, 25), DocumentHighlightKind::WRITE),
+            (range(4, 32, 36), DocumentHighlightKind::READ),
+            (range(5, 5, 9), DocumentHighlightKind::WRITE),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_document_highlight_keycodes() {
+    let found = highlights(4, 22).await;
+    assert_eq!(
+        found,
+        vec![
+            (range(4, 21, 27), DocumentHighlightKind::READ),
+            (range(5, 20, 26), DocumentHighlightKind::READ),
+        ]
+    );
+
+    let found = highlights(1, 25).await;
+    assert_eq!(
+        found,
+        vec![
+            (range(1, 23, 30), DocumentHighlightKind::WRITE),
+            (range(4, 39, 46), DocumentHighlightKind::READ),
+        ]
+    );
+
+    // Punctuation has nothing to highlight.
+    assert!(highlights(4, 4).await.is_empty());
+}
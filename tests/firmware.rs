@@ -0,0 +1,77 @@
+mod common;
+
+use qmk_lsp::config::Settings;
+use std::fs;
+use tower_lsp::lsp_types::*;
+
+const KEYCODES_H: &str = r#"#pragma once
+enum qk_keycode_defines {
+    KC_ESCAPE = 0x0029,
+    QK_BOOT = 0x7C00,
+};
+#define KC_ESC KC_ESCAPE
+"#;
+
+const QUANTUM_KEYCODES_H: &str = "#define LT(layer, kc) (QK_LAYER_TAP | (((layer)&0xF) << 8) | ((kc)&0xFF))\n";
+
+const ACTION_LAYER_H: &str = r#"#pragma once
+void layer_on(uint8_t layer);
+static inline bool layer_helper(void) { return layer_on(0); }
+"#;
+
+const KEYMAP: &str = "[0] = LAYOUT(KC_ESC, QK_BOOT, LT(1, KC_A)),\nvoid f(void) { layer_on(1); }\n";
+
+async fn definition_at(line: u32, character: u32) -> Option<(String, Position)> {
+    let dir = common::temp_dir("test_qmk_lsp_firmware");
+    let firmware = dir.join("qmk_firmware");
+    fs::create_dir_all(firmware.join("quantum")).unwrap();
+    fs::write(firmware.join("quantum/keycodes.h"), KEYCODES_H).unwrap();
+    fs::write(firmware.join("quantum/quantum_keycodes.h"), QUANTUM_KEYCODES_H).unwrap();
+    fs::write(firmware.join("quantum/action_layer.h"), ACTION_LAYER_H).unwrap();
+    fs::create_dir_all(dir.join("keymap")).unwrap();
+
+    let uri = Url::from_file_path(dir.join("keymap/keymap.c")).unwrap();
+    let mut service = common::start(InitializeParams {
+        initialization_options: Some(serde_json::json!({ "qmkLsp": { "firmwarePath": firmware } })),
+        ..Default::default()
+    })
+    .await;
+    common::open(&mut service, &uri, KEYMAP).await;
+
+    let params = GotoDefinitionParams {
+        text_document_position_params: common::position_params(&uri, line, character),
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    let response: Option<GotoDefinitionResponse> = common::request(&mut service, "textDocument/definition", params).await;
+    let _ = fs::remove_dir_all(&dir);
+    match response? {
+        GotoDefinitionResponse::Scalar(location) => {
+            let path = location.uri.to_file_path().unwrap();
+            let relative = path.strip_prefix(&firmware).unwrap().to_string_lossy().to_string();
+            Some((relative, location.range.start))
+        }
+        _ => panic!("Expected a single location"),
+    }
+}
+
+#[tokio::test]
+async fn test_definition_in_firmware() {
+    assert_eq!(definition_at(0, 15).await, Some(("quantum/keycodes.h".to_string(), Position::new(5, 8))));
+    assert_eq!(definition_at(0, 22).await, Some(("quantum/keycodes.h".to_string(), Position::new(3, 4))));
+    assert_eq!(definition_at(0, 30).await, Some(("quantum/quantum_keycodes.h".to_string(), Position::new(0, 8))));
+    assert_eq!(definition_at(1, 16).await, Some(("quantum/action_layer.h".to_string(), Position::new(1, 5))));
+    // Unknown to the firmware too.
+    assert_eq!(definition_at(1, 6).await, None);
+}
+
+#[test]
+fn test_settings_from_value() {
+    let nested = serde_json::json!({ "qmkLsp": { "firmwarePath": "/qmk" } });
+    assert_eq!(Settings::from_value(Some(&nested)).firmware_path, Some("/qmk".into()));
+    let flat = serde_json::json!({ "firmwarePath": "/qmk" });
+    assert_eq!(Settings::from_value(Some(&flat)).firmware_path, Some("/qmk".into()));
+    let invalid = serde_json::json!({ "firmwarePath": 3 });
+    assert_eq!(Settings::from_value(Some(&invalid)).firmware_path, None);
+    assert_eq!(Settings::from_value(None).firmware_path, None);
+}
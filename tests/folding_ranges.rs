@@ -0,0 +1,100 @@
+mod common;
+
+use tower_lsp::lsp_types::*;
+
+const KEYMAP: &str = "enum layers {
+    _BASE,
+    _NAV,
+};
+
+const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {
+    [_BASE] = LAYOUT(
+        KC_A, LCTL(LSFT(KC_B)),
+        MO(_NAV), KC_C
+    ),
+    [_NAV] = LAYOUT(KC_TRNS, KC_TRNS, KC_TRNS, KC_TRNS)
+};
+
+#ifdef COMBO_ENABLE
+combo_t key_combos[] = {
+    COMBO(ab_esc, KC_ESC),
+};
+#else
+// No combos.
+#endif
+";
+
+async fn open() -> (Url, tower_lsp::LspService<qmk_lsp::server::Backend>) {
+    let uri = Url::parse("file:///test_folding/keymap.c").unwrap();
+    let mut service = common::start(InitializeParams::default()).await;
+    common::open(&mut service, &uri, KEYMAP).await;
+    (uri, service)
+}
+
+#[tokio::test]
+async fn test_folding_ranges() {
+    let (uri, mut service) = open().await;
+    let params = FoldingRangeParams {
+        text_document: TextDocumentIdentifier { uri },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    let ranges: Vec<FoldingRange> = common::request(&mut service, "textDocument/foldingRange", params).await;
+    let folds: Vec<(u32, u32, Option<FoldingRangeKind>)> = ranges
+        .into_iter()
+        .map(|r| (r.start_line, r.end_line, r.kind))
+        .collect();
+    assert_eq!(
+        folds,
+        vec![
+            (0, 2, None),
+            (5, 10, None),
+            (6, 8, None),
+            (13, 16, Some(FoldingRangeKind::Region)),
+            (14, 15, None),
+            (17, 18, Some(FoldingRangeKind::Region)),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_selection_ranges() {
+    let (uri, mut service) = open().await;
+    let params = SelectionRangeParams {
+        text_document: TextDocumentIdentifier { uri },
+        // Inside `KC_B` in `LCTL(LSFT(KC_B))`.
+        positions: vec![Position::new(7, 26)],
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    let ranges: Vec<SelectionRange> = common::request(&mut service, "textDocument/selectionRange", params).await;
+    let mut texts = Vec::new();
+    let mut current = ranges.into_iter().next();
+    while let Some(range) = current {
+        texts.push(text(range.range));
+        current = range.parent.map(|p| *p);
+    }
+    assert_eq!(texts[0], "KC_B");
+    assert_eq!(texts[1], "LSFT(KC_B)");
+    assert_eq!(texts[2], "LCTL(LSFT(KC_B))");
+    assert!(texts[3].starts_with("LAYOUT(") && texts[3].ends_with(')'));
+    assert!(texts[4].starts_with("[_BASE] = LAYOUT("));
+    assert!(texts[5].starts_with('{') && texts[5].ends_with('}'));
+    assert!(texts[6].starts_with("const uint16_t PROGMEM keymaps") && texts[6].ends_with("};"));
+    assert_eq!(texts.len(), 7);
+}
+
+fn text(range: Range) -> String {
+    let lines: Vec<&str> = KEYMAP.lines().collect();
+    if range.start.line == range.end.line {
+        return lines[range.start.line as usize][range.start.character as usize..range.end.character as usize].to_string();
+    }
+    let mut text = lines[range.start.line as usize][range.start.character as usize..].to_string();
+    for line in &lines[range.start.line as usize + 1..range.end.line as usize] {
+        text.push('\n');
+        text.push_str(line);
+    }
+    text.push('\n');
+    text.push_str(&lines[range.end.line as usize][..range.end.character as usize]);
+    text
+}
@@ -0,0 +1,145 @@
+mod common;
+
+use qmk_lsp::document::LineIndex;
+use std::fs;
+use tower_lsp::lsp_types::*;
+
+/// Two halves of three keys over two rows, plus a two key thumb row; and the
+/// same halves listed one after the other, ErgoDox style.
+const INFO_JSON: &str = r#"{
+  "layouts": {
+    "LAYOUT_split": {
+      "layout": [
+        { "x": 0, "y": 0 }, { "x": 1, "y": 0 }, { "x": 2, "y": 0 },
+        { "x": 6, "y": 0 }, { "x": 7, "y": 0 }, { "x": 8, "y": 0 },
+        { "x": 0, "y": 1 }, { "x": 1, "y": 1 }, { "x": 2, "y": 1 },
+        { "x": 6, "y": 1 }, { "x": 7, "y": 1 }, { "x": 8, "y": 1 },
+        { "x": 2.5, "y": 2 }, { "x": 5.5, "y": 2 }
+      ]
+    },
+    "LAYOUT_ergodox": {
+      "layout": [
+        { "x": 0, "y": 0 }, { "x": 1, "y": 0 }, { "x": 2, "y": 0.25 },
+        { "x": 0, "y": 1 }, { "x": 1, "y": 1 }, { "x": 2, "y": 1.25 },
+        { "x": 6, "y": 0.25 }, { "x": 7, "y": 0 }, { "x": 8, "y": 0 },
+        { "x": 6, "y": 1.25 }, { "x": 7, "y": 1 }, { "x": 8, "y": 1 }
+      ]
+    }
+  }
+}"#;
+
+const KEYMAP: &str = "const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {
+    [0] = LAYOUT_split( // base layer
+        KC_Q, KC_W, KC_E, KC_I, KC_O, KC_P,
+      // home row
+      LGUI_T(KC_A), KC_S, /* d */ KC_D,   KC_K, KC_L, KC_SCLN, // mods
+            KC_SPC, MO(1)
+    ),
+    [1] = LAYOUT_other(KC_A,   KC_B)
+};
+";
+
+const FORMATTED: &str = "const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {
+    [0] = LAYOUT_split( // base layer
+        KC_Q,         KC_W, KC_E,                  KC_I, KC_O, KC_P,
+        // home row
+        LGUI_T(KC_A), KC_S, /* d */ KC_D,          KC_K, KC_L, KC_SCLN, // mods
+                            KC_SPC,          MO(1)
+    ),
+    [1] = LAYOUT_other(KC_A,   KC_B)
+};
+";
+
+fn apply(content: &str, edits: &[TextEdit]) -> String {
+    let index = LineIndex::new(content);
+    let mut result = content.to_string();
+    let mut edits = edits.to_vec();
+    edits.sort_by_key(|e| std::cmp::Reverse((e.range.start.line, e.range.start.character)));
+    for edit in edits {
+        let start = index.offset(content, edit.range.start);
+        let end = index.offset(content, edit.range.end);
+        result.replace_range(start..end, &edit.new_text);
+    }
+    result
+}
+
+async fn format(text: &str) -> Vec<TextEdit> {
+    let dir = common::temp_dir("test_qmk_lsp_format");
+    fs::write(dir.join("info.json"), INFO_JSON).unwrap();
+    let uri = Url::from_file_path(dir.join("keymap.c")).unwrap();
+    let mut service = common::start(InitializeParams::default()).await;
+    common::open(&mut service, &uri, text).await;
+
+    let params = DocumentFormattingParams {
+        text_document: TextDocumentIdentifier { uri },
+        options: FormattingOptions {
+            tab_size: 4,
+            insert_spaces: true,
+            ..Default::default()
+        },
+        work_done_progress_params: Default::default(),
+    };
+    let edits: Option<Vec<TextEdit>> = common::request(&mut service, "textDocument/formatting", params).await;
+    let _ = fs::remove_dir_all(&dir);
+    edits.unwrap()
+}
+
+#[tokio::test]
+async fn test_format_layout_grid() {
+    let edits = format(KEYMAP).await;
+    assert_eq!(edits.len(), 1);
+    assert_eq!(apply(KEYMAP, &edits), FORMATTED);
+
+    // Formatting again changes nothing.
+    assert!(format(FORMATTED).await.is_empty());
+}
+
+const SPLIT_KEYMAP: &str = "const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {
+    [0] = LAYOUT_ergodox(
+        KC_Q, KC_W, KC_E, KC_A, KC_S, KC_D,
+        KC_I, KC_O, KC_P, KC_K, KC_L, KC_SCLN
+    )
+};
+";
+
+const SPLIT_FORMATTED: &str = "const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {
+    [0] = LAYOUT_ergodox(
+        KC_Q, KC_W, KC_E,
+        KC_A, KC_S, KC_D,
+                             KC_I, KC_O, KC_P,
+                             KC_K, KC_L, KC_SCLN
+    )
+};
+";
+
+#[tokio::test]
+async fn test_format_split_halves() {
+    let edits = format(SPLIT_KEYMAP).await;
+    assert_eq!(apply(SPLIT_KEYMAP, &edits), SPLIT_FORMATTED);
+    assert!(format(SPLIT_FORMATTED).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_range_formatting() {
+    let dir = common::temp_dir("test_qmk_lsp_range_format");
+    fs::write(dir.join("info.json"), INFO_JSON).unwrap();
+    let uri = Url::from_file_path(dir.join("keymap.c")).unwrap();
+    let mut service = common::start(InitializeParams::default()).await;
+    common::open(&mut service, &uri, KEYMAP).await;
+
+    let params = |line: u32| DocumentRangeFormattingParams {
+        text_document: TextDocumentIdentifier { uri: uri.clone() },
+        range: Range::new(Position::new(line, 0), Position::new(line, 4)),
+        options: FormattingOptions {
+            tab_size: 4,
+            insert_spaces: true,
+            ..Default::default()
+        },
+        work_done_progress_params: Default::default(),
+    };
+    let inside: Option<Vec<TextEdit>> = common::request(&mut service, "textDocument/rangeFormatting", params(3)).await;
+    assert_eq!(inside.unwrap().len(), 1);
+    let outside: Option<Vec<TextEdit>> = common::request(&mut service, "textDocument/rangeFormatting", params(8)).await;
+    assert!(outside.unwrap().is_empty());
+    let _ = fs::remove_dir_all(&dir);
+}
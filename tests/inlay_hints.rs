@@ -0,0 +1,202 @@
+mod common;
+
+use std::fs;
+use tower_lsp::lsp_types::*;
+use tower_lsp::{jsonrpc, LspService};
+use tower_service::Service;
+
+const INFO_JSON: &str = r#"{
+  "layouts": {
+    "LAYOUT_2x2": {
+      "layout": [
+        { "matrix": [0, 0], "x": 0, "y": 0, "label": "Esc" },
+        { "matrix": [0, 1], "x": 1, "y": 0, "label": "Q" },
+        { "matrix": [1, 0], "x": 0, "y": 1, "label": "Tab" },
+        { "matrix": [1, 1], "x": 1, "y": 1 }
+      ]
+    }
+  }
+}"#;
+
+const KEYMAP: &str = "const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {
+    [0] = LAYOUT_2x2(
+        KC_ESC, KC_Q,
+        KC_TAB, LT(1, KC_A)
+    ),
+    [1] = LAYOUT_missing(KC_A)
+};
+";
+
+async fn start(options: Option<serde_json::Value>) -> (std::path::PathBuf, Url, LspService<qmk_lsp::server::Backend>) {
+    let dir = common::temp_dir("test_qmk_lsp_inlay");
+    fs::write(dir.join("info.json"), INFO_JSON).unwrap();
+    let uri = Url::from_file_path(dir.join("keymap.c")).unwrap();
+    let mut service = common::start(InitializeParams {
+        initialization_options: options,
+        ..Default::default()
+    })
+    .await;
+    common::open(&mut service, &uri, KEYMAP).await;
+    (dir, uri, service)
+}
+
+async fn hints(service: &mut LspService<qmk_lsp::server::Backend>, uri: &Url, range: Range) -> Vec<(Position, String)> {
+    let params = InlayHintParams {
+        text_document: TextDocumentIdentifier { uri: uri.clone() },
+        range,
+        work_done_progress_params: Default::default(),
+    };
+    let hints: Vec<InlayHint> = common::request(service, "textDocument/inlayHint", params).await;
+    hints
+        .into_iter()
+        .map(|h| match h.label {
+            InlayHintLabel::String(label) => (h.position, label),
+            _ => panic!("Expected a plain label"),
+        })
+        .collect()
+}
+
+fn whole() -> Range {
+    Range::new(Position::new(0, 0), Position::new(10, 0))
+}
+
+fn labels(hints: &[(Position, String)]) -> Vec<&str> {
+    hints.iter().map(|(_, l)| l.as_str()).collect()
+}
+
+#[tokio::test]
+async fn test_inlay_hints_index() {
+    let (dir, uri, mut service) = start(None).await;
+    let found = hints(&mut service, &uri, whole()).await;
+    assert_eq!(labels(&found), vec!["row 0", "L0", "L1", "row 1", "L2", "L3", "L0"]);
+    assert_eq!(found[0].0, Position::new(2, 8));
+    assert_eq!(found[4].0, Position::new(3, 8));
+    assert_eq!(found[5].0, Position::new(3, 16));
+
+    // Only the keys inside the requested range.
+    let found = hints(&mut service, &uri, Range::new(Position::new(3, 0), Position::new(4, 0))).await;
+    assert_eq!(labels(&found), vec!["row 1", "L2", "L3"]);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn test_inlay_hints_styles() {
+    let (dir, uri, mut service) = start(Some(serde_json::json!({ "inlayHints": "matrix" }))).await;
+    let found = hints(&mut service, &uri, whole()).await;
+    assert_eq!(labels(&found), vec!["row 0", "R0C0", "R0C1", "row 1", "R1C0", "R1C1", "L0"]);
+
+    let params = DidChangeConfigurationParams {
+        settings: serde_json::json!({ "qmkLsp": { "inlayHints": "label" } }),
+    };
+    let req = jsonrpc::Request::build("workspace/didChangeConfiguration")
+        .params(serde_json::to_value(params).unwrap())
+        .finish();
+    let _ = service.call(req).await.unwrap();
+
+    // The last key has no label and falls back to its index.
+    let found = hints(&mut service, &uri, whole()).await;
+    assert_eq!(labels(&found), vec!["row 0", "Esc", "Q", "row 1", "Tab", "L3", "L0"]);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+const LAYERED: &str = "enum layers { _BASE, _NAV, _SYM };
+const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {
+    [_BASE] = LAYOUT_2x2(MO(_NAV), KC_Q, KC_TAB, TG(_SYM)),
+    [_SYM] = LAYOUT_2x2(KC_1, KC_2, KC_3, KC_4),
+    [_NAV] = LAYOUT_2x2(KC_LEFT, KC_DOWN, KC_UP, KC_RGHT)
+};
+// _NAV in a comment
+";
+
+#[tokio::test]
+async fn test_layer_index_hints() {
+    let (dir, uri, mut service) = start(None).await;
+    common::open(&mut service, &uri, LAYERED).await;
+
+    let params = InlayHintParams {
+        text_document: TextDocumentIdentifier { uri: uri.clone() },
+        range: whole(),
+        work_done_progress_params: Default::default(),
+    };
+    let found: Vec<InlayHint> = common::request(&mut service, "textDocument/inlayHint", params).await;
+    let layer_hints: Vec<(Position, String)> = found
+        .iter()
+        .filter(|h| h.kind.is_none())
+        .filter_map(|h| match &h.label {
+            InlayHintLabel::String(label) if label.starts_with('=') => Some((h.position, label.clone())),
+            _ => None,
+        })
+        .collect();
+    let expected = [
+        ((0, 19), "=0"),
+        ((0, 25), "=1"),
+        ((0, 31), "=2"),
+        ((2, 10), "=0"),
+        ((2, 32), "=1"),
+        ((2, 56), "=2"),
+        ((3, 9), "=2 (block 1)"),
+        ((4, 9), "=1 (block 2)"),
+    ];
+    let expected: Vec<(Position, String)> = expected
+        .iter()
+        .map(|((line, character), label)| (Position::new(*line, *character), label.to_string()))
+        .collect();
+    assert_eq!(layer_hints, expected);
+
+    let tooltip = found
+        .iter()
+        .find(|h| h.position == Position::new(3, 9))
+        .and_then(|h| h.tooltip.clone())
+        .unwrap();
+    let InlayHintTooltip::MarkupContent(tooltip) = tooltip else {
+        panic!("Expected markdown tooltip");
+    };
+    assert!(tooltip.value.contains("`LAYOUT_2x2` · 4 keys"));
+    assert!(tooltip.value.contains("Block 1 of `keymaps`"));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+/// The layer index hints of a keymap whose layer enum lives in an included `layers.h`.
+async fn header_layer_hints(name: &str, rooted: bool) -> Vec<(Position, String)> {
+    let dir = common::temp_dir(name);
+    let keymap_dir = dir.join("keymaps/default");
+    fs::create_dir_all(&keymap_dir).unwrap();
+    fs::write(keymap_dir.join("layers.h"), "enum layers { _BASE, _NAV };\n").unwrap();
+    let text = "#include \"layers.h\"
+const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {
+    [_BASE] = LAYOUT_2x2(MO(_NAV), KC_Q, KC_TAB, KC_A),
+    [_NAV] = LAYOUT_2x2(KC_LEFT, KC_DOWN, KC_UP, KC_RGHT)
+};
+";
+    let uri = Url::from_file_path(keymap_dir.join("keymap.c")).unwrap();
+    let mut service = common::start(InitializeParams {
+        root_uri: rooted.then(|| Url::from_file_path(&dir).unwrap()),
+        ..Default::default()
+    })
+    .await;
+    common::open(&mut service, &uri, text).await;
+
+    let found = hints(&mut service, &uri, whole()).await;
+    let _ = fs::remove_dir_all(&dir);
+    found.into_iter().filter(|(_, l)| l.starts_with('=')).collect()
+}
+
+fn expected_header_hints() -> Vec<(Position, String)> {
+    vec![
+        (Position::new(2, 10), "=0".to_string()),
+        (Position::new(2, 32), "=1".to_string()),
+        (Position::new(3, 9), "=1".to_string()),
+    ]
+}
+
+#[tokio::test]
+async fn test_layer_index_hints_from_header() {
+    let found = header_layer_hints("test_qmk_lsp_inlay_header", true).await;
+    assert_eq!(found, expected_header_hints());
+}
+
+#[tokio::test]
+async fn test_layer_index_hints_from_header_without_root() {
+    let found = header_layer_hints("test_qmk_lsp_inlay_header_no_root", false).await;
+    assert_eq!(found, expected_header_hints());
+}
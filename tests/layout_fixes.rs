@@ -0,0 +1,85 @@
+mod common;
+
+use qmk_lsp::features::layout_fixes::LAYER_MISMATCH;
+use std::fs;
+use tower_lsp::lsp_types::*;
+
+const INFO_JSON: &str = r#"{
+  "layouts": {
+    "LAYOUT_3": { "layout": [{ "x": 0, "y": 0 }, { "x": 1, "y": 0 }, { "x": 2, "y": 0 }] },
+    "LAYOUT_2": { "layout": [{ "x": 0, "y": 0 }, { "x": 1, "y": 0 }] },
+    "LAYOUT_5": { "layout": [{ "x": 0, "y": 0 }, { "x": 1, "y": 0 }, { "x": 2, "y": 0 }, { "x": 3, "y": 0 }, { "x": 4, "y": 0 }] }
+  }
+}"#;
+
+const KEYMAP: &str = "const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {
+    [0] = LAYOUT_3(KC_A, KC_B),
+    [1] = LAYOUT_3(KC_A, KC_B, KC_C, KC_D /* extra */, KC_E)
+};
+";
+
+async fn actions(line: u32) -> Vec<(String, bool, TextEdit)> {
+    let dir = common::temp_dir("test_qmk_lsp_layout_fixes");
+    fs::write(dir.join("info.json"), INFO_JSON).unwrap();
+    let uri = Url::from_file_path(dir.join("keymap.c")).unwrap();
+    let mut service = common::start(InitializeParams::default()).await;
+    common::open(&mut service, &uri, KEYMAP).await;
+
+    let diagnostic = Diagnostic {
+        range: Range::new(Position::new(line, 4), Position::new(line, 4)),
+        code: Some(NumberOrString::String(LAYER_MISMATCH.to_string())),
+        ..Default::default()
+    };
+    let params = CodeActionParams {
+        text_document: TextDocumentIdentifier { uri: uri.clone() },
+        range: diagnostic.range,
+        context: CodeActionContext {
+            diagnostics: vec![diagnostic],
+            ..Default::default()
+        },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    let actions: Option<CodeActionResponse> = common::request(&mut service, "textDocument/codeAction", params).await;
+    let _ = fs::remove_dir_all(&dir);
+
+    actions
+        .unwrap_or_default()
+        .into_iter()
+        .map(|a| match a {
+            CodeActionOrCommand::CodeAction(a) => {
+                let mut changes = a.edit.unwrap().changes.unwrap();
+                let edit = changes.remove(&uri).unwrap().remove(0);
+                (a.title, a.is_preferred == Some(true), edit)
+            }
+            CodeActionOrCommand::Command(_) => panic!("Expected a code action"),
+        })
+        .collect()
+}
+
+fn range(line: u32, start: u32, end: u32) -> Range {
+    Range::new(Position::new(line, start), Position::new(line, end))
+}
+
+#[tokio::test]
+async fn test_layer_mismatch_padding() {
+    let found = actions(1).await;
+    assert_eq!(found.len(), 3);
+    assert_eq!(found[0].0, "Switch to 'LAYOUT_2' (2 keys)");
+    assert!(found[0].1);
+    assert_eq!(found[0].2, TextEdit::new(range(1, 10, 18), "LAYOUT_2".to_string()));
+    assert_eq!(found[1].0, "Pad '0' with 1 × KC_NO");
+    assert_eq!(found[1].2, TextEdit::new(range(1, 29, 29), ", KC_NO".to_string()));
+    assert_eq!(found[2].0, "Pad '0' with 1 × _______");
+    assert_eq!(found[2].2, TextEdit::new(range(1, 29, 29), ", _______".to_string()));
+}
+
+#[tokio::test]
+async fn test_layer_mismatch_trimming() {
+    let found = actions(2).await;
+    assert_eq!(found.len(), 2);
+    assert_eq!(found[0].0, "Switch to 'LAYOUT_5' (5 keys)");
+    assert_eq!(found[1].0, "Remove the 2 extra keys from '1'");
+    assert!(!found[1].1);
+    assert_eq!(found[1].2, TextEdit::new(range(2, 35, 59), String::new()));
+}
@@ -0,0 +1,30 @@
+use qmk_lsp::legend::{humanize, key_legend, LegendContext};
+use qmk_lsp::parser::keycodes::load_keycodes;
+use qmk_lsp::parser::macros::{load_macros, load_mod_masks};
+
+#[test]
+fn test_key_legends() {
+    let keycodes = load_keycodes();
+    let macros = load_macros();
+    let mod_masks = load_mod_masks();
+    let ctx = LegendContext { keycodes: &keycodes, macros: &macros, mod_masks: &mod_masks };
+
+    assert_eq!(key_legend("KC_BSPC", &ctx), "⌫");
+    assert_eq!(key_legend("KC_LSFT", &ctx), "⇧");
+    assert_eq!(key_legend("KC_A", &ctx), "A");
+    assert_eq!(key_legend("LT(_NAV, KC_SPC)", &ctx), "Spc / Nav");
+    assert_eq!(key_legend("LCTL_T(KC_ESC)", &ctx), "Esc / ⌃");
+    assert_eq!(key_legend("LCTL(LSFT(KC_T))", &ctx), "⌃⇧T");
+    assert_eq!(key_legend("OSM(MOD_LCTL | MOD_LSFT)", &ctx), "OSM ⌃⇧");
+    assert_eq!(key_legend("MO(2)", &ctx), "2");
+    assert_eq!(key_legend("_______", &ctx), "▽");
+    assert_eq!(key_legend("KC_VIM_SAVE", &ctx), "Vim Save");
+    assert_eq!(key_legend("MY_MACRO(KC_A)", &ctx), "MY_MACRO(A)");
+}
+
+#[test]
+fn test_humanize() {
+    assert_eq!(humanize("_NAV"), "Nav");
+    assert_eq!(humanize("_GAMING_L2"), "Gaming L2");
+    assert_eq!(humanize("___"), "___");
+}
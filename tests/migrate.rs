@@ -0,0 +1,110 @@
+mod common;
+
+use qmk_lsp::features::migrate::{find_deprecated, migrate_source, DEPRECATED_KEYCODE};
+use qmk_lsp::parser::keycodes::load_keycodes;
+use qmk_lsp::parser::layers::parse_layers;
+use qmk_lsp::parser::macros::load_macros;
+use qmk_lsp::parser::tokens::tokenize;
+use tower_lsp::lsp_types::*;
+
+const KEYMAP: &str = "\
+[0] = LAYOUT(
+    RESET, KC_GESC, RGB_TOG, // KC_LEAD stays in comments
+    QK_BOOT, KC_A
+)";
+
+#[test]
+fn test_find_deprecated() {
+    let keycodes = load_keycodes();
+    let macros = load_macros();
+    let found = find_deprecated(KEYMAP, &tokenize(KEYMAP), &parse_layers(KEYMAP), &macros, &keycodes);
+    let names: Vec<&str> = found.iter().map(|u| u.keycode.name.as_str()).collect();
+    assert_eq!(names, vec!["RESET", "KC_GESC", "RGB_TOG"]);
+    assert_eq!(found[2].replacements, ["UG_TOGG", "RM_TOGG"]);
+}
+
+#[test]
+fn test_migrate_source() {
+    let keycodes = load_keycodes();
+    let edits = migrate_source(KEYMAP, &keycodes, &load_macros());
+    let replaced: Vec<&str> = edits.iter().map(|e| e.new_text.as_str()).collect();
+    assert_eq!(replaced, vec!["QK_BOOT", "QK_GESC", "UG_TOGG"]);
+    assert_eq!(edits[0].range, Range::new(Position::new(1, 4), Position::new(1, 9)));
+}
+
+#[test]
+fn test_find_deprecated_skips_non_keycodes() {
+    let source = "\
+#ifdef DEBUG
+#define RESET 1
+#define MY_KEYS KC_A, \\
+    RESET
+#endif
+int RESET;
+const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {
+    [0] = LAYOUT(RESET, LT(1, KC_GESC), MO(1))
+};
+bool process_record_user(uint16_t keycode, keyrecord_t *record) {
+    switch (keycode) {
+        case RGB_TOG:
+            return DEBUG;
+    }
+    return true;
+}";
+    let keycodes = load_keycodes();
+    let macros = load_macros();
+    let found = find_deprecated(source, &tokenize(source), &parse_layers(source), &macros, &keycodes);
+    let names: Vec<&str> = found.iter().map(|u| u.keycode.name.as_str()).collect();
+    assert_eq!(names, vec!["RESET", "KC_GESC", "RGB_TOG"]);
+    assert_eq!(found[0].span.start, source.find("LAYOUT(RESET").unwrap() + 7);
+}
+
+async fn code_action_titles(range: Range) -> Vec<String> {
+    let dir = common::temp_dir("test_qmk_lsp_migrate");
+    let uri = Url::from_file_path(dir.join("keymap.c")).unwrap();
+    let mut service = common::start(InitializeParams::default()).await;
+    common::open(&mut service, &uri, KEYMAP).await;
+
+    let diagnostic = Diagnostic {
+        range,
+        code: Some(NumberOrString::String(DEPRECATED_KEYCODE.to_string())),
+        ..Default::default()
+    };
+    let params = CodeActionParams {
+        text_document: TextDocumentIdentifier { uri: uri.clone() },
+        range: diagnostic.range,
+        context: CodeActionContext {
+            diagnostics: vec![diagnostic],
+            ..Default::default()
+        },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    let actions: Option<CodeActionResponse> = common::request(&mut service, "textDocument/codeAction", params).await;
+    let _ = std::fs::remove_dir_all(&dir);
+
+    actions
+        .unwrap_or_default()
+        .into_iter()
+        .map(|a| match a {
+            CodeActionOrCommand::CodeAction(a) => a.title,
+            CodeActionOrCommand::Command(c) => c.title,
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_deprecated_code_actions() {
+    let titles = code_action_titles(Range::new(Position::new(1, 20), Position::new(1, 27))).await;
+    assert_eq!(titles, vec![
+        "Replace 'RGB_TOG' with 'UG_TOGG'",
+        "Replace 'RGB_TOG' with 'RM_TOGG'",
+        "Replace all 3 deprecated keycodes in this file",
+    ]);
+}
+
+#[tokio::test]
+async fn test_deprecated_code_actions_stale_range() {
+    let titles = code_action_titles(Range::new(Position::new(1, 27), Position::new(1, 20))).await;
+    assert!(titles.is_empty());
+}
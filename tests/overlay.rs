@@ -0,0 +1,100 @@
+use qmk_lsp::parser::keycodes::load_keycodes;
+use qmk_lsp::parser::macros::load_macros;
+use qmk_lsp::parser::overlay::{load_overlays, OverlayCache};
+use std::fs;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[test]
+fn test_overlays_merge_nearest_last() {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let workspace = std::env::temp_dir().join(format!("test_qmk_lsp_overlay_{}", now));
+    let keyboard = workspace.join("keyboards").join("mykb");
+    fs::create_dir_all(workspace.join(".qmk-lsp")).unwrap();
+    fs::create_dir_all(keyboard.join(".qmk-lsp")).unwrap();
+
+    fs::write(workspace.join(".qmk-lsp/keycodes.json"), r#"[
+        {"name": "QK_KB_0", "description": "Workspace", "doc": "", "category": "custom"},
+        {"name": "KC_HOUSE", "description": "House key", "doc": "", "category": "custom"}
+    ]"#).unwrap();
+    fs::write(keyboard.join(".qmk-lsp/keycodes.json"), r#"[
+        {"name": "QK_KB_0", "description": "Keyboard", "doc": "", "category": "custom"}
+    ]"#).unwrap();
+    fs::write(keyboard.join(".qmk-lsp/macros.json"), r#"[
+        {"name": "HOME_ROW", "detail": "House macro", "description": "", "doc": ""}
+    ]"#).unwrap();
+
+    let keycodes = Arc::new(load_keycodes());
+    let macros = Arc::new(load_macros());
+    let overlays = load_overlays(&keyboard.join("keymap.c"), &keycodes, &macros);
+
+    let _ = fs::remove_dir_all(&workspace);
+
+    assert!(overlays.errors.is_empty());
+    assert_eq!(overlays.keycodes["QK_KB_0"].description, "Keyboard");
+    assert!(overlays.keycodes.contains_key("KC_HOUSE"));
+    assert!(overlays.keycodes.contains_key("KC_A"));
+    assert!(overlays.macros.contains_key("HOME_ROW"));
+    assert!(overlays.macros.contains_key("LT"));
+    assert!(!keycodes.contains_key("KC_HOUSE"));
+}
+
+#[test]
+fn test_overlays_absent_or_invalid() {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let dir = std::env::temp_dir().join(format!("test_qmk_lsp_overlay_bad_{}", now));
+    fs::create_dir_all(&dir).unwrap();
+
+    let keycodes = Arc::new(load_keycodes());
+    let macros = Arc::new(load_macros());
+
+    let overlays = load_overlays(&dir.join("keymap.c"), &keycodes, &macros);
+    assert!(Arc::ptr_eq(&overlays.keycodes, &keycodes));
+    assert!(Arc::ptr_eq(&overlays.macros, &macros));
+
+    fs::create_dir_all(dir.join(".qmk-lsp")).unwrap();
+    fs::write(dir.join(".qmk-lsp/macros.json"), "[{\"name\": \"BROKEN\"}]").unwrap();
+    let overlays = load_overlays(&dir.join("keymap.c"), &keycodes, &macros);
+
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(overlays.errors.len(), 1);
+    assert!(Arc::ptr_eq(&overlays.macros, &macros));
+}
+
+#[test]
+fn test_overlay_cache() {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let dir = std::env::temp_dir().join(format!("test_qmk_lsp_overlay_cache_{}", now));
+    fs::create_dir_all(dir.join(".qmk-lsp")).unwrap();
+    fs::create_dir_all(dir.join("other")).unwrap();
+    fs::write(dir.join(".qmk-lsp/keycodes.json"), r#"[
+        {"name": "KC_HOUSE", "description": "House key", "doc": "", "category": "custom"}
+    ]"#).unwrap();
+
+    let keycodes = Arc::new(load_keycodes());
+    let macros = Arc::new(load_macros());
+    let cache = OverlayCache::default();
+
+    let (first, loaded) = cache.get(&dir.join("keymap.c"), &keycodes, &macros);
+    assert!(loaded);
+    assert!(first.keycodes.contains_key("KC_HOUSE"));
+
+    // Documents under the same overlay directories share the merged maps.
+    let (again, loaded) = cache.get(&dir.join("keymap.c"), &keycodes, &macros);
+    assert!(!loaded);
+    assert!(Arc::ptr_eq(&first, &again));
+    let (sibling, loaded) = cache.get(&dir.join("other/keymap.c"), &keycodes, &macros);
+    assert!(!loaded);
+    assert!(Arc::ptr_eq(&first, &sibling));
+
+    // Changes are only picked up once the cache is cleared.
+    fs::remove_file(dir.join(".qmk-lsp/keycodes.json")).unwrap();
+    assert!(cache.get(&dir.join("keymap.c"), &keycodes, &macros).0.keycodes.contains_key("KC_HOUSE"));
+    cache.clear();
+    let (reloaded, loaded) = cache.get(&dir.join("keymap.c"), &keycodes, &macros);
+    let _ = fs::remove_dir_all(&dir);
+
+    assert!(loaded);
+    assert!(!reloaded.keycodes.contains_key("KC_HOUSE"));
+}
@@ -1,5 +1,11 @@
 use qmk_lsp::parser::layers::parse_layers;
-use qmk_lsp::parser::custom::parse_custom_keycodes;
+use qmk_lsp::parser::custom::{custom_keycodes_from_enums, parse_custom_keycodes};
+use qmk_lsp::parser::defines::{parse_conditional_branches, parse_defines};
+use qmk_lsp::parser::enums::{find_layer_enum, parse_enums};
+use qmk_lsp::parser::includes::parse_includes;
+use qmk_lsp::parser::macros::{load_macros, load_mod_masks, validate_macros, Macro, ParamKind, MAX_LAYER};
+use qmk_lsp::parser::tables::{parse_table, COMBO_TABLE, TAP_DANCE_TABLE};
+use qmk_lsp::parser::tokens::tokenize;
 
 #[test]
 fn test_parse_layers() {
@@ -33,3 +39,156 @@ fn test_parse_custom_keycodes() {
     assert_eq!(kcs[1].name, "MY_KEY2");
     assert_eq!(kcs[2].name, "MY_KEY3");
 }
+
+#[test]
+fn test_macro_params() {
+    let macros = load_macros();
+    let lt = &macros["LT"];
+    assert_eq!(lt.signature(), "LT(layer, kc)");
+    assert_eq!(lt.params[0].kind, ParamKind::Layer { max: 15 });
+    assert_eq!(lt.params[1].kind, ParamKind::BasicKeycode);
+    assert_eq!(macros["MO"].params[0].kind, ParamKind::Layer { max: MAX_LAYER });
+    assert_eq!(macros["TD"].params[0].kind, ParamKind::TapDance);
+    assert_eq!(lt.params[0].kind.describe(), "layer 0–15");
+}
+
+#[test]
+fn test_validate_macros() {
+    let parse = |json: &str| -> Vec<Macro> { serde_json::from_str(json).unwrap() };
+
+    let ok = parse(r#"[{"name": "UC", "detail": "", "description": "", "doc": "",
+        "params": [{"name": "codepoint", "kind": "int", "min": 0, "max": 1114111}]}]"#);
+    assert!(validate_macros(&ok).is_ok());
+
+    let empty_range = parse(r#"[{"name": "X", "detail": "", "description": "", "doc": "",
+        "params": [{"name": "n", "kind": "int", "min": 5, "max": 1}]}]"#);
+    assert!(validate_macros(&empty_range).is_err());
+
+    let duplicate_param = parse(r#"[{"name": "X", "detail": "", "description": "", "doc": "",
+        "params": [{"name": "kc", "kind": "keycode"}, {"name": "kc", "kind": "keycode"}]}]"#);
+    assert!(validate_macros(&duplicate_param).is_err());
+
+    let big_layer = parse(r#"[{"name": "X", "detail": "", "description": "", "doc": "",
+        "params": [{"name": "layer", "kind": "layer", "max": 64}]}]"#);
+    assert!(validate_macros(&big_layer).is_err());
+
+    let unknown_kind = serde_json::from_str::<Vec<Macro>>(r#"[{"name": "X", "detail": "", "description": "", "doc": "",
+        "params": [{"name": "p", "kind": "banana"}]}]"#);
+    assert!(unknown_kind.is_err());
+}
+
+#[test]
+fn test_functional_macro_family() {
+    let macros = load_macros();
+    for name in ["MT", "OSM", "OSL", "TT", "LM", "PDF", "C", "S", "A", "G", "HYPR", "MEH", "LCS", "LCAG", "RCS", "UC", "UM", "UP", "HYPR_T", "ALL_T", "SH_T", "QK_LAYER_TAP_GET_LAYER"] {
+        assert!(macros.contains_key(name), "missing {}", name);
+    }
+    assert_eq!(macros["MT"].params[0].kind, ParamKind::ModMask);
+    assert_eq!(macros["LM"].signature(), "LM(layer, mod)");
+    assert_eq!(macros["UP"].params.len(), 2);
+
+    let masks = load_mod_masks();
+    assert!(masks.contains_key("MOD_LCTL"));
+    assert!(masks.contains_key("MOD_HYPR"));
+}
+
+#[test]
+fn test_parse_enums() {
+    let content = "
+    enum layers { _BASE, _NAV };
+    enum my_keycodes {
+        VIM_SAVE = SAFE_RANGE,
+        VIM_QUIT,
+    };
+    enum { UNNAMED = (1 << 2) };
+    ";
+    let enums = parse_enums(&tokenize(content));
+    assert_eq!(enums.len(), 3);
+    assert_eq!(enums[0].name.as_deref(), Some("layers"));
+    assert_eq!(enums[2].name, None);
+    assert_eq!(enums[2].members[0].value.as_deref(), Some("(1<<2)"));
+
+    let layer_enum = find_layer_enum(&enums, &[]).unwrap();
+    assert_eq!(layer_enum.members.len(), 2);
+
+    let custom = custom_keycodes_from_enums(&enums);
+    let names: Vec<&str> = custom.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, vec!["VIM_SAVE", "VIM_QUIT"]);
+    assert_eq!(&content[custom[1].span.clone()], "VIM_QUIT");
+}
+
+#[test]
+fn test_parse_tap_dances() {
+    let content = "
+    tap_dance_action_t tap_dance_actions[] = {
+        [TD_ESC_CAPS] = ACTION_TAP_DANCE_DOUBLE(KC_ESC, KC_CAPS),
+        [TD_Q] = ACTION_TAP_DANCE_FN_ADVANCED(NULL, q_finished, q_reset),
+    };
+    ";
+    let tap_dances = parse_table(&tokenize(content), TAP_DANCE_TABLE).unwrap();
+    let names: Vec<&str> = tap_dances.entries.iter().map(|t| t.name.as_str()).collect();
+    assert_eq!(names, vec!["TD_ESC_CAPS", "TD_Q"]);
+}
+
+#[test]
+fn test_parse_combos() {
+    let content = "
+    extern combo_t key_combos[];
+    combo_t key_combos[] = {
+        COMBO(jk_esc, KC_ESC),
+        [CB_QW] = COMBO(qw_tab, KC_TAB),
+        COMBO_ACTION(df_combo),
+    };
+    ";
+    let combos = parse_table(&tokenize(content), COMBO_TABLE).unwrap();
+    let names: Vec<&str> = combos.entries.iter().map(|t| t.name.as_str()).collect();
+    assert_eq!(names, vec!["jk_esc", "CB_QW", "df_combo"]);
+    assert!(content[combos.span.clone()].starts_with("key_combos[] = {"));
+}
+
+#[test]
+fn test_parse_includes() {
+    let content = "#include QMK_KEYBOARD_H\n#include \"layers.h\"\n  # include \"users/me.h\"\n#include <stdio.h>\n";
+    let includes = parse_includes(content);
+    let paths: Vec<&str> = includes.iter().map(|i| i.path.as_str()).collect();
+    assert_eq!(paths, vec!["layers.h", "users/me.h"]);
+    assert_eq!(&content[includes[0].span.clone()], "layers.h");
+}
+
+#[test]
+fn test_parse_defines() {
+    let content = "#define HOME_A LGUI_T(KC_A) // gui\n#define URL \"https://qmk.fm\"\n#define TWO(a, b) \\\n    a b\n#pragma once\n";
+    let defines = parse_defines(content);
+    assert_eq!(defines.len(), 3);
+    assert_eq!(defines[0].name, "HOME_A");
+    assert_eq!(defines[0].value, "LGUI_T(KC_A)");
+    assert_eq!(&content[defines[0].name_span.clone()], "HOME_A");
+    assert_eq!(defines[1].value, "\"https://qmk.fm\"");
+    assert_eq!(defines[2].params, Some(vec!["a".to_string(), "b".to_string()]));
+    assert_eq!(defines[2].value, "a b");
+    assert!(content[defines[2].span.clone()].ends_with("a b"));
+}
+
+#[test]
+fn test_parse_conditional_branches() {
+    let content = "#ifdef RGB_MATRIX_ENABLE\n#  if RGB_MATRIX_LED_COUNT > 10\nA\n#  endif\n#else\nB\n#endif\n#if 0\n";
+    let branches: Vec<&str> = parse_conditional_branches(content)
+        .into_iter()
+        .map(|b| &content[b])
+        .collect();
+    assert_eq!(
+        branches,
+        vec![
+            "#ifdef RGB_MATRIX_ENABLE\n#  if RGB_MATRIX_LED_COUNT > 10\nA\n#  endif\n",
+            "#  if RGB_MATRIX_LED_COUNT > 10\nA\n",
+            "#else\nB\n",
+        ]
+    );
+}
+
+#[test]
+fn test_enum_values() {
+    let content = "enum layers { _BASE, _NAV = 4, _SYM, _ALIAS = _NAV, _HEX = 0x10, _USER = SAFE_RANGE, _NEXT };";
+    let enums = parse_enums(&tokenize(content));
+    assert_eq!(enums[0].values(), vec![Some(0), Some(4), Some(5), Some(4), Some(16), None, None]);
+}
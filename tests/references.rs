@@ -0,0 +1,107 @@
+mod common;
+
+use std::fs;
+use tower_lsp::lsp_types::*;
+
+const LAYERS_H: &str = "enum layers { _BASE, _NAV };\n";
+
+const USER_H: &str = "enum userspace_keycodes { VIM_SAVE = SAFE_RANGE };\n";
+
+const KEYMAP: &str = r#"#include QMK_KEYBOARD_H
+#include "layers.h"
+#include "me.h"
+
+const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {
+    [_BASE] = LAYOUT(MO(_NAV), LT(_NAV, KC_SPC), VIM_SAVE),
+    [_NAV] = LAYOUT(_______, TG(_BASE), KC_NO)
+};
+
+// _NAV in a comment is not a reference
+layer_state_t layer_state_set_user(layer_state_t state) {
+    switch (get_highest_layer(state)) {
+    case _NAV:
+        if (IS_LAYER_ON(_NAV)) layer_on(_NAV);
+        break;
+    }
+    return state;
+}
+
+bool process_record_user(uint16_t keycode, keyrecord_t *record) {
+    switch (keycode) {
+    case VIM_SAVE:
+        return false;
+    }
+    return true;
+}
+"#;
+
+async fn references_at(line: u32, character: u32, include_declaration: bool) -> Option<Vec<Location>> {
+    let dir = common::temp_dir("test_qmk_lsp_references");
+    let keymap_dir = dir.join("keyboards/kb/keymaps/vim");
+    fs::create_dir_all(&keymap_dir).unwrap();
+    fs::create_dir_all(dir.join("users/me")).unwrap();
+    fs::write(keymap_dir.join("layers.h"), LAYERS_H).unwrap();
+    fs::write(dir.join("users/me/me.h"), USER_H).unwrap();
+
+    let uri = Url::from_file_path(keymap_dir.join("keymap.c")).unwrap();
+    let mut service = common::start(InitializeParams {
+        root_uri: Some(Url::from_file_path(&dir).unwrap()),
+        ..Default::default()
+    })
+    .await;
+    common::open(&mut service, &uri, KEYMAP).await;
+
+    let params = ReferenceParams {
+        text_document_position: common::position_params(&uri, line, character),
+        context: ReferenceContext { include_declaration },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    let locations = common::request(&mut service, "textDocument/references", params).await;
+    let _ = fs::remove_dir_all(&dir);
+    locations
+}
+
+fn lines(locations: &[Location]) -> Vec<(String, u32)> {
+    locations
+        .iter()
+        .map(|l| {
+            let file = l.uri.path().rsplit('/').next().unwrap().to_string();
+            (file, l.range.start.line)
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_layer_references() {
+    // On `_NAV` inside MO(...)
+    let locations = references_at(5, 24, true).await.unwrap();
+    let found = lines(&locations);
+    let keymap = |line: u32| ("keymap.c".to_string(), line);
+    assert_eq!(
+        found,
+        vec![keymap(5), keymap(5), keymap(6), keymap(12), keymap(13), keymap(13), ("layers.h".to_string(), 0)]
+    );
+    let decl = locations.last().unwrap();
+    assert_eq!(decl.range, Range::new(Position::new(0, 21), Position::new(0, 25)));
+
+    let without_decl = references_at(5, 24, false).await.unwrap();
+    assert_eq!(without_decl.len(), 6);
+}
+
+#[tokio::test]
+async fn test_custom_keycode_references() {
+    // On `VIM_SAVE` in the case label
+    let locations = references_at(21, 10, true).await.unwrap();
+    let found = lines(&locations);
+    assert_eq!(
+        found,
+        vec![("keymap.c".to_string(), 5), ("keymap.c".to_string(), 21), ("me.h".to_string(), 0)]
+    );
+}
+
+#[tokio::test]
+async fn test_no_references_for_builtin_keycodes() {
+    // On `KC_SPC`
+    assert!(references_at(5, 40, true).await.is_none());
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use std::fs;
+use tower_lsp::lsp_types::*;
+use tower_lsp::{jsonrpc, LspService};
+use tower_service::Service;
+
+const LAYERS_H: &str = "enum layers { _BASE, _LOWER };\n";
+
+const KEYMAP: &str = r#"#include "layers.h"
+enum custom_keycodes { VIM_SAVE = SAFE_RANGE };
+const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {
+    [_BASE] = LAYOUT(MO(_LOWER), VIM_SAVE),
+    [_LOWER] = LAYOUT(_______, KC_NO) // back to _BASE from _LOWER
+};
+const char *name = "_LOWER";
+"#;
+
+struct Fixture {
+    dir: std::path::PathBuf,
+    uri: Url,
+    service: LspService<qmk_lsp::server::Backend>,
+}
+
+async fn setup() -> Fixture {
+    let dir = common::temp_dir("test_qmk_lsp_rename");
+    fs::write(dir.join("layers.h"), LAYERS_H).unwrap();
+    let uri = Url::from_file_path(dir.join("keymap.c")).unwrap();
+    let mut service = common::start(InitializeParams::default()).await;
+    common::open(&mut service, &uri, KEYMAP).await;
+    Fixture { dir, uri, service }
+}
+
+async fn rename(fixture: &mut Fixture, line: u32, character: u32, new_name: &str) -> jsonrpc::Response {
+    let params = RenameParams {
+        text_document_position: common::position_params(&fixture.uri, line, character),
+        new_name: new_name.to_string(),
+        work_done_progress_params: Default::default(),
+    };
+    let req = jsonrpc::Request::build("textDocument/rename")
+        .params(serde_json::to_value(params).unwrap())
+        .id(1)
+        .finish();
+    fixture.service.call(req).await.unwrap().unwrap()
+}
+
+#[tokio::test]
+async fn test_rename_layer() {
+    let mut fixture = setup().await;
+
+    let prepared: Option<PrepareRenameResponse> = common::request(
+        &mut fixture.service,
+        "textDocument/prepareRename",
+        common::position_params(&fixture.uri, 3, 26),
+    )
+    .await;
+    let Some(PrepareRenameResponse::Range(range)) = prepared else {
+        panic!("Expected a range");
+    };
+    assert_eq!(range, Range::new(Position::new(3, 24), Position::new(3, 30)));
+
+    let response = rename(&mut fixture, 3, 26, "_SYM").await;
+    let (_, result) = response.into_parts();
+    let edit: WorkspaceEdit = serde_json::from_value(result.unwrap()).unwrap();
+    let changes = edit.changes.unwrap();
+
+    // The comment and the string keep the old name.
+    let keymap = &changes[&fixture.uri];
+    let lines: Vec<u32> = keymap.iter().map(|e| e.range.start.line).collect();
+    assert_eq!(lines, vec![3, 4]);
+    assert!(keymap.iter().all(|e| e.new_text == "_SYM"));
+
+    let header = Url::from_file_path(fixture.dir.join("layers.h")).unwrap();
+    assert_eq!(changes[&header][0].range, Range::new(Position::new(0, 21), Position::new(0, 27)));
+    let _ = fs::remove_dir_all(&fixture.dir);
+}
+
+#[tokio::test]
+async fn test_rename_refuses_collisions() {
+    let mut fixture = setup().await;
+
+    for new_name in ["KC_A", "LT", "_BASE", "2FA", "switch"] {
+        let response = rename(&mut fixture, 3, 33, new_name).await;
+        let (_, result) = response.into_parts();
+        assert!(result.is_err(), "{} should be refused", new_name);
+    }
+
+    let response = rename(&mut fixture, 3, 33, "VIM_WRITE").await;
+    let (_, result) = response.into_parts();
+    let edit: WorkspaceEdit = serde_json::from_value(result.unwrap()).unwrap();
+    assert_eq!(edit.changes.unwrap()[&fixture.uri].len(), 2);
+    let _ = fs::remove_dir_all(&fixture.dir);
+}
+
+#[tokio::test]
+async fn test_prepare_rename_builtin_keycode() {
+    let mut fixture = setup().await;
+    // On `KC_NO`
+    let prepared: Option<PrepareRenameResponse> = common::request(
+        &mut fixture.service,
+        "textDocument/prepareRename",
+        common::position_params(&fixture.uri, 4, 32),
+    )
+    .await;
+    assert!(prepared.is_none());
+    let _ = fs::remove_dir_all(&fixture.dir);
+}
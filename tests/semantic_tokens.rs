@@ -0,0 +1,122 @@
+mod common;
+
+use qmk_lsp::features::semantic_tokens::{TOKEN_MODIFIERS, TOKEN_TYPES};
+use tower_lsp::lsp_types::*;
+
+const KEYMAP: &str = "enum layers { _BASE, _NAV };
+enum custom_keycodes { VIM_SAVE = SAFE_RANGE };
+const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {
+    [_BASE] = LAYOUT(KC_A, KC_LSFT, KC_MPLY, QK_BOOT, RGB_TOG),
+    [_NAV] = LAYOUT(_______, XXXXXXX, MO(_NAV), MT(MOD_LCTL, KC_B), VIM_SAVE)
+};
+";
+
+/// Decodes tokens into (line, start, length, type, modifiers).
+fn decode(data: &[SemanticToken]) -> Vec<(u32, u32, u32, &'static str, Vec<&'static str>)> {
+    let mut line = 0;
+    let mut start = 0;
+    data.iter()
+        .map(|t| {
+            if t.delta_line > 0 {
+                line += t.delta_line;
+                start = t.delta_start;
+            } else {
+                start += t.delta_start;
+            }
+            let modifiers = TOKEN_MODIFIERS
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| t.token_modifiers_bitset & (1 << i) != 0)
+                .map(|(_, m)| *m)
+                .collect();
+            (line, start, t.length, TOKEN_TYPES[t.token_type as usize], modifiers)
+        })
+        .collect()
+}
+
+async fn tokens(range: Option<Range>) -> Vec<SemanticToken> {
+    let dir = common::temp_dir("test_qmk_lsp_semantic");
+    let uri = Url::from_file_path(dir.join("keymap.c")).unwrap();
+    let mut service = common::start(InitializeParams::default()).await;
+    common::open(&mut service, &uri, KEYMAP).await;
+
+    let text_document = TextDocumentIdentifier { uri };
+    let data = match range {
+        None => {
+            let params = SemanticTokensParams {
+                text_document,
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            };
+            match common::request(&mut service, "textDocument/semanticTokens/full", params).await {
+                Some(SemanticTokensResult::Tokens(tokens)) => tokens.data,
+                _ => panic!("Expected tokens"),
+            }
+        }
+        Some(range) => {
+            let params = SemanticTokensRangeParams {
+                text_document,
+                range,
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            };
+            match common::request(&mut service, "textDocument/semanticTokens/range", params).await {
+                Some(SemanticTokensRangeResult::Tokens(tokens)) => tokens.data,
+                _ => panic!("Expected tokens"),
+            }
+        }
+    };
+    let _ = std::fs::remove_dir_all(&dir);
+    data
+}
+
+#[tokio::test]
+async fn test_semantic_tokens_full() {
+    let decoded = decode(&tokens(None).await);
+    let on_line = |line: u32| -> Vec<(&str, Vec<&str>)> {
+        decoded
+            .iter()
+            .filter(|t| t.0 == line)
+            .map(|t| (t.3, t.4.clone()))
+            .collect()
+    };
+
+    assert_eq!(on_line(0), vec![("layer", vec![]), ("layer", vec![])]);
+    assert_eq!(on_line(1), vec![("customKeycode", vec![])]);
+    assert_eq!(
+        on_line(3),
+        vec![
+            ("layer", vec![]),
+            ("layoutMacro", vec![]),
+            ("keycode", vec![]),
+            ("modifierKeycode", vec![]),
+            ("mediaKeycode", vec![]),
+            ("quantumKeycode", vec![]),
+            ("lightingKeycode", vec!["deprecated"]),
+        ]
+    );
+    assert_eq!(
+        on_line(4),
+        vec![
+            ("layer", vec![]),
+            ("layoutMacro", vec![]),
+            ("keycode", vec!["transparent"]),
+            ("keycode", vec!["noop"]),
+            ("functionalMacro", vec![]),
+            ("layer", vec![]),
+            ("functionalMacro", vec![]),
+            ("modMask", vec![]),
+            ("keycode", vec![]),
+            ("customKeycode", vec![]),
+        ]
+    );
+
+    let nav = decoded.iter().find(|t| t.0 == 4 && t.1 == 5).unwrap();
+    assert_eq!(nav.2, 4);
+}
+
+#[tokio::test]
+async fn test_semantic_tokens_range() {
+    let decoded = decode(&tokens(Some(Range::new(Position::new(1, 0), Position::new(2, 0)))).await);
+    assert_eq!(decoded, vec![(1, 23, 8, "customKeycode", vec![])]);
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use tower_lsp::lsp_types::*;
+
+const KEYMAP: &str = "[0] = LAYOUT(LT(1, KC_A), OSM(MOD_LSFT), KC_B, MO(1, 2))";
+
+async fn signature_at(character: u32) -> Option<SignatureHelp> {
+    let dir = common::temp_dir("test_qmk_lsp_signature");
+    let uri = Url::from_file_path(dir.join("keymap.c")).unwrap();
+    let mut service = common::start(InitializeParams::default()).await;
+    common::open(&mut service, &uri, KEYMAP).await;
+
+    let params = SignatureHelpParams {
+        context: None,
+        text_document_position_params: common::position_params(&uri, 0, character),
+        work_done_progress_params: Default::default(),
+    };
+    let help = common::request(&mut service, "textDocument/signatureHelp", params).await;
+    let _ = std::fs::remove_dir_all(&dir);
+    help
+}
+
+fn parameter_doc(help: &SignatureHelp, index: usize) -> String {
+    let params = help.signatures[0].parameters.as_ref().unwrap();
+    match &params[index].documentation {
+        Some(Documentation::String(doc)) => doc.clone(),
+        _ => panic!("Expected plain documentation"),
+    }
+}
+
+#[tokio::test]
+async fn test_signature_help_layer_tap() {
+    // Right after "LT("
+    let help = signature_at(16).await.unwrap();
+    assert_eq!(help.signatures[0].label, "LT(layer, kc)");
+    assert_eq!(help.active_parameter, Some(0));
+    assert_eq!(parameter_doc(&help, 0), "layer: layer 0–15");
+    let params = help.signatures[0].parameters.as_ref().unwrap();
+    assert_eq!(params[1].label, ParameterLabel::LabelOffsets([10, 12]));
+
+    // Right after "LT(1, "
+    let help = signature_at(19).await.unwrap();
+    assert_eq!(help.active_parameter, Some(1));
+    assert_eq!(parameter_doc(&help, 1), "kc: basic keycode");
+}
+
+#[tokio::test]
+async fn test_signature_help_nested_and_layout() {
+    // Inside OSM( and directly inside LAYOUT(
+    let help = signature_at(30).await.unwrap();
+    assert_eq!(help.signatures[0].label, "OSM(mod)");
+    assert!(signature_at(13).await.is_none());
+}
+
+#[tokio::test]
+async fn test_signature_help_past_last_parameter() {
+    // Right after "MO(1, ": no signature rather than one highlighting `layer`.
+    assert!(signature_at(53).await.is_none());
+    // Right after "MO(" the parameter is still active.
+    let help = signature_at(50).await.unwrap();
+    assert_eq!(help.signatures[0].label, "MO(layer)");
+    assert_eq!(help.active_parameter, Some(0));
+}
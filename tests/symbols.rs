@@ -0,0 +1,58 @@
+mod common;
+
+use tower_lsp::lsp_types::*;
+
+const KEYMAP: &str = r#"enum layers { _BASE, _NAV };
+enum custom_keycodes { VIM_SAVE = SAFE_RANGE };
+tap_dance_action_t tap_dance_actions[] = {
+    [TD_ESC] = ACTION_TAP_DANCE_DOUBLE(KC_ESC, KC_CAPS),
+};
+const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {
+    [_BASE] = LAYOUT(KC_A, KC_B),
+    [_NAV] = LAYOUT(KC_LEFT, VIM_SAVE)
+};
+combo_t key_combos[] = { COMBO(jk_esc, KC_ESC) };
+"#;
+
+#[tokio::test]
+async fn test_document_symbols() {
+    let dir = common::temp_dir("test_qmk_lsp_symbols");
+    let uri = Url::from_file_path(dir.join("keymap.c")).unwrap();
+    let mut service = common::start(InitializeParams::default()).await;
+    common::open(&mut service, &uri, KEYMAP).await;
+
+    let params = DocumentSymbolParams {
+        text_document: TextDocumentIdentifier { uri },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    let response: Option<DocumentSymbolResponse> =
+        common::request(&mut service, "textDocument/documentSymbol", params).await;
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let Some(DocumentSymbolResponse::Nested(symbols)) = response else {
+        panic!("Expected nested symbols");
+    };
+    let names: Vec<&str> = symbols.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, vec!["layers", "custom_keycodes", "tap_dance_actions", "keymaps", "key_combos"]);
+
+    assert_eq!(symbols[0].detail.as_deref(), Some("Layers"));
+    assert_eq!(symbols[1].detail.as_deref(), Some("Custom Keycodes"));
+    let members = symbols[1].children.as_ref().unwrap();
+    assert_eq!(members[0].name, "VIM_SAVE");
+    assert_eq!(members[0].detail.as_deref(), Some("SAFE_RANGE"));
+
+    let keymaps = &symbols[3];
+    assert_eq!(keymaps.kind, SymbolKind::ARRAY);
+    assert_eq!(keymaps.range.start, Position::new(5, 23));
+    assert_eq!(keymaps.range.end, Position::new(8, 1));
+    let layers = keymaps.children.as_ref().unwrap();
+    assert_eq!(layers[1].name, "_NAV");
+    assert_eq!(layers[1].detail.as_deref(), Some("LAYOUT · 2 keys"));
+    assert_eq!(layers[1].selection_range, Range::new(Position::new(7, 5), Position::new(7, 9)));
+    assert_eq!(layers[1].range.start, Position::new(7, 4));
+
+    let combos = symbols[4].children.as_ref().unwrap();
+    assert_eq!(combos[0].name, "jk_esc");
+    assert_eq!(symbols[2].children.as_ref().unwrap()[0].name, "TD_ESC");
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use qmk_lsp::features::typos::{edit_distance, locale_prefix, suggestions, unknown_keycodes, UNKNOWN_KEYCODE};
+use qmk_lsp::parser::keycodes::load_keycodes;
+use qmk_lsp::parser::layers::parse_layers;
+use qmk_lsp::parser::tokens::tokenize;
+use std::fs;
+use tower_lsp::lsp_types::*;
+
+#[test]
+fn test_edit_distance() {
+    assert_eq!(edit_distance("KC_ENTR", "KC_ENT"), 1);
+    assert_eq!(edit_distance("KC_ENTR", "KC_ENTER"), 1);
+    assert_eq!(edit_distance("KC_SAPC", "KC_SPAC"), 1);
+    assert_eq!(edit_distance("KC_A", "KC_LEFT"), 4);
+}
+
+#[test]
+fn test_suggestions() {
+    let keycodes = load_keycodes();
+    assert_eq!(suggestions("KC_ENTR", &keycodes, &[])[..2], ["KC_ENT", "KC_ENTER"]);
+    assert_eq!(suggestions("kc_a", &keycodes, &[])[0], "KC_A");
+    assert_eq!(suggestions("KC_MACR0", &keycodes, &["KC_MACRO".to_string()])[0], "KC_MACRO");
+    assert!(suggestions("KC_NOTHING_LIKE_IT", &keycodes, &[]).is_empty());
+}
+
+#[test]
+fn test_unknown_keycodes() {
+    let text = "\
+const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {
+    [0] = LAYOUT(kc_a, KC_A, LT(1, kc_b), DE_Z, DE_ADIAA, KC_NOPE)
+};
+bool process_record_user(uint16_t keycode, keyrecord_t *record) {
+    uint16_t kc_pressed = KC_FOO;
+    return DE_WHATEVER != kc_pressed;
+}";
+    let keycodes = load_keycodes();
+    let locale = vec!["DE_Z".to_string(), "DE_ADIA".to_string(), "DE_Y".to_string()];
+    let tokens = tokenize(text);
+    let unknown: Vec<&str> = unknown_keycodes(&tokens, &parse_layers(text), &locale, |name| keycodes.contains_key(name))
+        .into_iter()
+        .map(|t| t.text.as_str())
+        .collect();
+    assert_eq!(unknown, vec!["kc_a", "kc_b", "DE_ADIAA", "KC_NOPE", "KC_FOO"]);
+
+    // Without a locale header, DE_ names are left alone.
+    assert!(unknown_keycodes(&tokens, &parse_layers(text), &[], |name| keycodes.contains_key(name))
+        .iter()
+        .all(|t| !t.text.starts_with("DE_")));
+    assert_eq!(locale_prefix(&locale), Some("DE_"));
+}
+
+const KEYMAP: &str = "#include \"keymap_german.h\"
+enum custom_keycodes { KC_MACRO = SAFE_RANGE };
+const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {
+    [0] = LAYOUT(KC_ENTR, kc_a, KC_MACR, DE_ADIAA, DE_Z)
+};
+";
+
+async fn actions(character: u32, len: u32) -> Vec<(String, bool)> {
+    actions_in(Range::new(Position::new(3, character), Position::new(3, character + len))).await
+}
+
+async fn actions_in(range: Range) -> Vec<(String, bool)> {
+    let dir = common::temp_dir("test_qmk_lsp_typos");
+    let firmware = dir.join("qmk_firmware");
+    fs::create_dir_all(firmware.join("quantum/keymap_extras")).unwrap();
+    fs::write(firmware.join("quantum/keycodes.h"), "#pragma once\n").unwrap();
+    fs::write(firmware.join("quantum/keymap_extras/keymap_german.h"), "#define DE_Z KC_Y\n#define DE_Y KC_Z\n#define DE_ADIA KC_QUOT\n").unwrap();
+    let uri = Url::from_file_path(dir.join("keymap.c")).unwrap();
+    let mut service = common::start(InitializeParams {
+        initialization_options: Some(serde_json::json!({ "qmkLsp": { "firmwarePath": firmware } })),
+        ..Default::default()
+    })
+    .await;
+    common::open(&mut service, &uri, KEYMAP).await;
+
+    let diagnostic = Diagnostic {
+        range,
+        code: Some(NumberOrString::String(UNKNOWN_KEYCODE.to_string())),
+        ..Default::default()
+    };
+    let params = CodeActionParams {
+        text_document: TextDocumentIdentifier { uri: uri.clone() },
+        range: diagnostic.range,
+        context: CodeActionContext {
+            diagnostics: vec![diagnostic],
+            ..Default::default()
+        },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    let actions: Option<CodeActionResponse> = common::request(&mut service, "textDocument/codeAction", params).await;
+    let _ = fs::remove_dir_all(&dir);
+
+    actions
+        .unwrap_or_default()
+        .into_iter()
+        .map(|a| match a {
+            CodeActionOrCommand::CodeAction(a) => (a.title, a.is_preferred == Some(true)),
+            CodeActionOrCommand::Command(c) => (c.title, false),
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_typo_code_actions() {
+    let found = actions(17, 7).await;
+    assert_eq!(found[0], ("Replace 'KC_ENTR' with 'KC_ENT'".to_string(), true));
+    assert_eq!(found[1], ("Replace 'KC_ENTR' with 'KC_ENTER'".to_string(), false));
+
+    let found = actions(26, 4).await;
+    assert_eq!(found[0], ("Replace 'kc_a' with 'KC_A'".to_string(), true));
+
+    // Custom keycodes from the keymap.
+    let found = actions(32, 7).await;
+    assert_eq!(found[0], ("Replace 'KC_MACR' with 'KC_MACRO'".to_string(), true));
+
+    // Locale keycodes from the included keymap_extras header.
+    let found = actions(41, 8).await;
+    assert_eq!(found[0], ("Replace 'DE_ADIAA' with 'DE_ADIA'".to_string(), true));
+}
+
+#[tokio::test]
+async fn test_typo_code_actions_stale_range() {
+    assert!(actions_in(Range::new(Position::new(3, 24), Position::new(3, 17))).await.is_empty());
+}
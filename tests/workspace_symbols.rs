@@ -0,0 +1,89 @@
+mod common;
+
+use qmk_lsp::features::workspace_symbols::fuzzy_score;
+use std::fs;
+use tower_lsp::lsp_types::*;
+
+const KEYMAP: &str = r#"enum layers { _BASE, _GAMING };
+const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {
+    [_BASE] = LAYOUT_split_3x6_3(KC_A, KC_VIM_SAVE),
+    [_GAMING] = LAYOUT_split_3x6_3(KC_W, KC_S)
+};
+"#;
+
+const USERSPACE: &str = "enum userspace_keycodes { KC_VIM_SAVE = SAFE_RANGE, KC_VIM_QUIT };\n";
+
+const INFO_JSON: &str = r#"{
+  "keyboard_name": "Corne",
+  "layouts": {
+    "LAYOUT_split_3x6_3": { "layout": [{ "x": 0, "y": 0 }] }
+  }
+}"#;
+
+async fn search(service: &mut tower_lsp::LspService<qmk_lsp::server::Backend>, query: &str) -> Vec<SymbolInformation> {
+    let params = WorkspaceSymbolParams {
+        query: query.to_string(),
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    common::request(service, "workspace/symbol", params).await
+}
+
+#[tokio::test]
+async fn test_workspace_symbols() {
+    let dir = common::temp_dir("test_qmk_lsp_workspace_symbols");
+    let keymap_dir = dir.join("keyboards/crkbd/keymaps/vim");
+    fs::create_dir_all(&keymap_dir).unwrap();
+    fs::create_dir_all(dir.join("users/me")).unwrap();
+    fs::write(keymap_dir.join("keymap.c"), KEYMAP).unwrap();
+    fs::write(dir.join("users/me/me.h"), USERSPACE).unwrap();
+    fs::write(dir.join("keyboards/crkbd/info.json"), INFO_JSON).unwrap();
+
+    let mut service = common::start(InitializeParams {
+        root_uri: Some(Url::from_file_path(&dir).unwrap()),
+        ..Default::default()
+    })
+    .await;
+
+    let gaming = search(&mut service, "gam").await;
+    assert_eq!(gaming[0].name, "_GAMING");
+    assert_eq!(gaming[0].kind, SymbolKind::OBJECT);
+    assert_eq!(gaming[0].container_name.as_deref(), Some("keyboards/crkbd/keymaps/vim"));
+    assert_eq!(gaming[0].location.range.start, Position::new(3, 5));
+
+    let save = search(&mut service, "vimsave").await;
+    assert_eq!(save[0].name, "KC_VIM_SAVE");
+    assert_eq!(save[0].kind, SymbolKind::ENUM_MEMBER);
+    assert_eq!(save[0].container_name.as_deref(), Some("users/me"));
+
+    let layouts = search(&mut service, "split_3x6").await;
+    let mut containers: Vec<&str> = layouts
+        .iter()
+        .filter(|s| s.name == "LAYOUT_split_3x6_3")
+        .map(|s| s.container_name.as_deref().unwrap())
+        .collect();
+    containers.sort();
+    assert_eq!(containers, vec!["Corne", "keyboards/crkbd/keymaps/vim"]);
+    let layout = layouts.iter().find(|s| s.kind == SymbolKind::STRUCT).unwrap();
+    assert_eq!(layout.location.range.start, Position::new(3, 5));
+
+    // Unsaved edits in open documents win over the indexed file.
+    let uri = Url::from_file_path(keymap_dir.join("keymap.c")).unwrap();
+    common::open(&mut service, &uri, &KEYMAP.replace("_GAMING", "_GAME")).await;
+    let gaming = search(&mut service, "gam").await;
+    let names: Vec<&str> = gaming.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, vec!["_GAME"]);
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_fuzzy_score() {
+    assert!(fuzzy_score("gam", "_GAMING").is_some());
+    assert!(fuzzy_score("vs", "KC_VIM_SAVE").is_some());
+    assert!(fuzzy_score("xyz", "_GAMING").is_none());
+
+    // Contiguous and word-start matches beat scattered ones.
+    assert!(fuzzy_score("nav", "_NAV") > fuzzy_score("nav", "_NUMPAD_AV"));
+    assert!(fuzzy_score("save", "VIM_SAVE") > fuzzy_score("save", "SHIFT_AV_EXE"));
+}